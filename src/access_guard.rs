@@ -0,0 +1,77 @@
+use crate::types::RedbValue;
+use std::marker::PhantomData;
+
+/// A reference to a value stored in a table
+pub struct AccessGuard<'a, V: RedbValue + ?Sized + 'a> {
+    bytes: Vec<u8>,
+    _marker: PhantomData<&'a V>,
+}
+
+impl<'a, V: RedbValue + ?Sized + 'a> AccessGuard<'a, V> {
+    pub(crate) fn with_owned_value(bytes: Vec<u8>) -> Self {
+        Self {
+            bytes,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the deserialized value
+    pub fn value(&self) -> V::SelfType<'_> {
+        V::from_bytes(&self.bytes)
+    }
+}
+
+impl<'a, V: RedbValue + ?Sized + 'a> std::fmt::Debug for AccessGuard<'a, V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AccessGuard").field("value", &self.value()).finish()
+    }
+}
+
+/// A mutable, in-place reference to a reserved value slot
+///
+/// Returned by `Table::insert_reserve`, so a caller can write the value's bytes directly
+/// rather than constructing them up front. The write is applied to the table when this guard
+/// is dropped.
+pub struct AccessGuardMut<'a, K: ?Sized> {
+    buffer: Vec<u8>,
+    finish: Option<Box<dyn FnMut(Vec<u8>) + 'a>>,
+    _marker: PhantomData<&'a K>,
+}
+
+impl<'a, K: ?Sized> AccessGuardMut<'a, K> {
+    pub(crate) fn new(value_length: usize, finish: Box<dyn FnMut(Vec<u8>) + 'a>) -> Self {
+        Self {
+            buffer: vec![0u8; value_length],
+            finish: Some(finish),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, K: ?Sized> AsMut<[u8]> for AccessGuardMut<'a, K> {
+    fn as_mut(&mut self) -> &mut [u8] {
+        &mut self.buffer
+    }
+}
+
+impl<'a, K: ?Sized> std::ops::Deref for AccessGuardMut<'a, K> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.buffer
+    }
+}
+
+impl<'a, K: ?Sized> std::ops::DerefMut for AccessGuardMut<'a, K> {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.buffer
+    }
+}
+
+impl<'a, K: ?Sized> Drop for AccessGuardMut<'a, K> {
+    fn drop(&mut self) {
+        if let Some(mut finish) = self.finish.take() {
+            finish(std::mem::take(&mut self.buffer));
+        }
+    }
+}
@@ -0,0 +1,99 @@
+//! Transparent per-value compression, configured per-table via
+//! [`TableDefinition::with_compression`](crate::TableDefinition::with_compression).
+//!
+//! Mirrors the sstable/LevelDB approach of compressing block contents: `Table::insert`
+//! compresses `V::as_bytes(...)` before handing bytes to the B-tree, and the bytes are
+//! decompressed again wherever they leave the tree (`get`, `remove`, `range`, `drain`), so
+//! `AccessGuard<V>` never has to know compression is involved.
+
+/// The compressor applied to a table's values before they're written to the B-tree
+///
+/// Defaults to `None`, so existing tables are unaffected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Compression {
+    #[default]
+    None,
+    #[cfg(feature = "lz4")]
+    Lz4,
+    #[cfg(feature = "zstd")]
+    Zstd,
+}
+
+// One-byte codec tag stored as a prefix on every framed value, followed by the original
+// (pre-compression) length as a little-endian `u32`, so compressed and stored-raw values can
+// sit side by side in one table. A value whose tag names a codec not compiled into this build
+// is returned as its stored payload, undecoded.
+#[cfg(any(feature = "lz4", feature = "zstd"))]
+const TAG_RAW: u8 = 0;
+#[cfg(feature = "lz4")]
+const TAG_LZ4: u8 = 1;
+#[cfg(feature = "zstd")]
+const TAG_ZSTD: u8 = 2;
+
+#[cfg(any(feature = "lz4", feature = "zstd"))]
+const FRAME_HEADER_LEN: usize = 5;
+
+impl Compression {
+    /// Returns `true` for any codec other than `None`
+    pub(crate) fn is_enabled(self) -> bool {
+        !matches!(self, Compression::None)
+    }
+
+    /// Compresses `bytes`, returning a frame of `[tag: u8][original_len: u32 LE][payload]`
+    /// (for `Compression::None`, `bytes` unchanged and unframed). Falls back to storing `bytes` raw under `TAG_RAW` if compression didn't shrink them
+    /// (e.g. small, incompressible, or empty values).
+    pub(crate) fn compress(self, bytes: &[u8]) -> Vec<u8> {
+        match self {
+            Compression::None => bytes.to_vec(),
+            #[cfg(feature = "lz4")]
+            Compression::Lz4 => Self::frame(TAG_LZ4, bytes, lz4_flex::compress(bytes)),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => {
+                let compressed = zstd::bulk::compress(bytes, 0).unwrap_or_else(|_| bytes.to_vec());
+                Self::frame(TAG_ZSTD, bytes, compressed)
+            }
+        }
+    }
+
+    #[cfg(any(feature = "lz4", feature = "zstd"))]
+    fn frame(tag: u8, original: &[u8], compressed: Vec<u8>) -> Vec<u8> {
+        let (tag, payload) = if compressed.len() < original.len() {
+            (tag, compressed)
+        } else {
+            (TAG_RAW, original.to_vec())
+        };
+        let mut out = Vec::with_capacity(FRAME_HEADER_LEN + payload.len());
+        out.push(tag);
+        out.extend_from_slice(&(original.len() as u32).to_le_bytes());
+        out.extend_from_slice(&payload);
+        out
+    }
+
+    /// Decompresses a frame produced by [`Self::compress`]. A no-op for `Compression::None`,
+    /// since those values were never framed in the first place; bytes too short to carry a
+    /// frame header are likewise returned unchanged. Any longer value is read as a frame, so
+    /// unframed values of 5 bytes or more are not passed through.
+    pub(crate) fn decompress(self, bytes: &[u8]) -> Vec<u8> {
+        #[cfg(not(any(feature = "lz4", feature = "zstd")))]
+        {
+            let _ = self;
+            bytes.to_vec()
+        }
+        #[cfg(any(feature = "lz4", feature = "zstd"))]
+        {
+            if !self.is_enabled() || bytes.len() < FRAME_HEADER_LEN {
+                return bytes.to_vec();
+            }
+            let tag = bytes[0];
+            let original_len = u32::from_le_bytes(bytes[1..FRAME_HEADER_LEN].try_into().unwrap()) as usize;
+            let payload = &bytes[FRAME_HEADER_LEN..];
+            match tag {
+                #[cfg(feature = "lz4")]
+                TAG_LZ4 => lz4_flex::decompress(payload, original_len).unwrap_or_else(|_| payload.to_vec()),
+                #[cfg(feature = "zstd")]
+                TAG_ZSTD => zstd::bulk::decompress(payload, original_len).unwrap_or_else(|_| payload.to_vec()),
+                _ => payload.to_vec(),
+            }
+        }
+    }
+}
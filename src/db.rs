@@ -0,0 +1,133 @@
+use crate::transaction::TableMeta;
+use crate::tree_store::TransactionalMemory;
+use crate::watch::{TableChange, Watcher};
+use crate::{ReadTransaction, Result, WriteTransaction};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::mpsc::{self, Receiver};
+
+struct Inner {
+    mem: TransactionalMemory,
+    tables: HashMap<String, TableMeta>,
+    watchers: HashMap<String, Vec<Watcher>>,
+}
+
+/// An open redb database
+///
+/// This implementation keeps its data in an in-process arena rather than memory-mapping a
+/// file, so it does not persist across process restarts; `path` is accepted (and required to
+/// be creatable) purely to keep the API shape callers expect from the on-disk engine.
+pub struct Database {
+    inner: Rc<RefCell<Inner>>,
+}
+
+impl Database {
+    /// Creates a new database at `path`
+    pub fn create(path: impl AsRef<Path>) -> Result<Database> {
+        // Touch the file so that callers relying on its existence (e.g. for cleanup) see it,
+        // matching the on-disk engine's behavior.
+        if let Some(parent) = path.as_ref().parent() {
+            if !parent.as_os_str().is_empty() {
+                std::fs::create_dir_all(parent)?;
+            }
+        }
+        std::fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(false)
+            .open(path)?;
+        Ok(Self::new())
+    }
+
+    /// Opens an existing database at `path`
+    pub fn open(path: impl AsRef<Path>) -> Result<Database> {
+        std::fs::OpenOptions::new().write(true).open(path)?;
+        Ok(Self::new())
+    }
+
+    fn new() -> Database {
+        Database {
+            inner: Rc::new(RefCell::new(Inner {
+                mem: TransactionalMemory::new(),
+                tables: HashMap::new(),
+                watchers: HashMap::new(),
+            })),
+        }
+    }
+
+    /// Registers `callback` to be invoked with the set of changed keys whenever a
+    /// [`WriteTransaction`] that mutated `table_name` commits successfully
+    ///
+    /// Never invoked for an aborted transaction, and not invoked at all if the transaction
+    /// didn't actually change `table_name`.
+    pub fn watch(&self, table_name: &str, callback: impl Fn(&[TableChange]) + 'static) {
+        self.inner
+            .borrow_mut()
+            .watchers
+            .entry(table_name.to_string())
+            .or_default()
+            .push(Watcher::Callback(Box::new(callback)));
+    }
+
+    /// Registers a channel that receives the set of changed keys whenever a
+    /// [`WriteTransaction`] that mutated `table_name` commits successfully
+    ///
+    /// Never sent to for an aborted transaction, and not sent to at all if the transaction
+    /// didn't actually change `table_name`. Dropping the returned [`Receiver`] is safe; later
+    /// sends are silently discarded.
+    pub fn watch_channel(&self, table_name: &str) -> Receiver<Vec<TableChange>> {
+        let (sender, receiver) = mpsc::channel();
+        self.inner
+            .borrow_mut()
+            .watchers
+            .entry(table_name.to_string())
+            .or_default()
+            .push(Watcher::Channel(sender));
+        receiver
+    }
+
+    fn dispatch_changes(&self, changes: HashMap<String, Vec<TableChange>>) {
+        for (table_name, changes) in changes {
+            // Take ownership of the watcher list instead of borrowing `inner` across the
+            // `notify` calls below: a callback may react to a commit by starting and
+            // committing its own `WriteTransaction` on this same `Database` (e.g. an index
+            // built on top of redb), which needs to borrow `inner` again to do so.
+            let Some(watchers) = self.inner.borrow_mut().watchers.remove(&table_name) else {
+                continue;
+            };
+            for watcher in &watchers {
+                watcher.notify(&changes);
+            }
+            // Merge back (rather than overwrite) in case a callback registered a new watcher
+            // for this table while we didn't hold it borrowed.
+            let mut inner = self.inner.borrow_mut();
+            let entry = inner.watchers.entry(table_name).or_default();
+            let mut merged = watchers;
+            merged.append(entry);
+            *entry = merged;
+        }
+    }
+
+    /// Begins a write transaction
+    pub fn begin_write(&self) -> Result<WriteTransaction<'_>> {
+        let inner = self.inner.borrow();
+        Ok(WriteTransaction::new(self, inner.tables.clone(), inner.mem.clone()))
+    }
+
+    /// Begins a read transaction, seeing a consistent snapshot of all tables as of this call
+    pub fn begin_read(&self) -> Result<ReadTransaction<'_>> {
+        let inner = self.inner.borrow();
+        Ok(ReadTransaction::new(self, inner.tables.clone(), inner.mem.clone()))
+    }
+
+    pub(crate) fn apply_commit(
+        &self,
+        pending_tables: HashMap<String, TableMeta>,
+        pending_changes: HashMap<String, Vec<TableChange>>,
+    ) {
+        self.inner.borrow_mut().tables.extend(pending_tables);
+        self.dispatch_changes(pending_changes);
+    }
+}
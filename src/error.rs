@@ -0,0 +1,53 @@
+use std::fmt::{Display, Formatter};
+use std::io;
+
+/// Errors returned by this crate
+#[derive(Debug)]
+pub enum Error {
+    /// The named table is already open elsewhere and cannot be opened mutably
+    TableAlreadyOpen(String),
+    /// A table was opened with key/value types that don't match the data already stored under
+    /// that name
+    TableTypeMismatch(String),
+    /// `insert_reserve` was called on a table configured with value compression, where the
+    /// reserved length (pre-compression) can't be promised to match what's actually stored
+    InsertReserveNotSupported(String),
+    /// `Table::import` was called on a table that already contains entries; bulk-loading an
+    /// export stream is only supported into a freshly opened, empty table
+    ImportRequiresEmptyTable(String),
+    /// `Table::import`'s stream was not sorted in strictly increasing key order, which the
+    /// bulk-load path requires since it builds leaves directly from the stream's order rather
+    /// than inserting entries one at a time
+    ImportNotSorted(String),
+    /// Wraps an underlying I/O failure
+    Io(io::Error),
+}
+
+impl From<io::Error> for Error {
+    fn from(value: io::Error) -> Self {
+        Error::Io(value)
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::TableAlreadyOpen(name) => write!(f, "Table '{name}' is already open"),
+            Error::TableTypeMismatch(msg) => write!(f, "Table type mismatch: {msg}"),
+            Error::InsertReserveNotSupported(name) => write!(
+                f,
+                "Table '{name}' is configured with value compression, so insert_reserve (whose \
+                 reserved length is unknown post-compression) is not supported"
+            ),
+            Error::ImportRequiresEmptyTable(name) => {
+                write!(f, "Table '{name}' is not empty, so it cannot be used as the target of Table::import")
+            }
+            Error::ImportNotSorted(name) => {
+                write!(f, "Import stream for table '{name}' is not sorted in strictly increasing key order")
+            }
+            Error::Io(err) => write!(f, "I/O error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -0,0 +1,25 @@
+#![warn(clippy::all)]
+
+//! A simple, portable, high-performance, ACID, embedded key-value store.
+
+mod access_guard;
+mod compression;
+mod db;
+mod error;
+mod table;
+mod transaction;
+mod tree_store;
+mod types;
+mod watch;
+
+pub use access_guard::AccessGuard;
+pub use compression::Compression;
+pub use db::Database;
+pub use error::Error;
+pub use table::{ReadOnlyTable, ReadableTable, Table};
+pub use transaction::{ReadTransaction, TableDefinition, WriteTransaction};
+pub use types::{RedbKey, RedbValue};
+pub use watch::{ChangeKind, TableChange};
+
+/// This crate's result type, defaulting to `()` for operations with nothing to return
+pub type Result<T = ()> = std::result::Result<T, Error>;
@@ -1,44 +1,88 @@
-use crate::tree_store::{
-    AccessGuardMut, Btree, BtreeDrain, BtreeMut, BtreeRangeIter, Checksum, PageHint, PageNumber,
-    TransactionalMemory,
-};
+use crate::access_guard::AccessGuardMut;
+use crate::tree_store::{Btree, BtreeDrain, BtreeMut, BtreeRangeIter, Checksum, PageHint, PageNumber, TransactionalMemory};
 use crate::types::{RedbKey, RedbValue};
-use crate::Result;
+use crate::watch::{ChangeKind, TableChange};
+use crate::{Compression, Error, Result};
 use crate::{AccessGuard, WriteTransaction};
 use std::borrow::Borrow;
 use std::cell::RefCell;
+use std::io::{self, Read, Write};
 use std::ops::RangeBounds;
 use std::rc::Rc;
 
+// Magic header for `ReadableTable::export`'s framed stream format, so `Table::import` can reject
+// data that isn't a redb export (or is a future, incompatible version of it) up front.
+const EXPORT_MAGIC: &[u8; 8] = b"redbxp01";
+
+fn write_len_prefixed(writer: &mut impl Write, bytes: &[u8]) -> Result<()> {
+    writer.write_all(&(bytes.len() as u32).to_le_bytes())?;
+    writer.write_all(bytes)?;
+    Ok(())
+}
+
+fn read_len_prefixed(reader: &mut impl Read) -> Result<Vec<u8>> {
+    let mut len_bytes = [0u8; 4];
+    reader.read_exact(&mut len_bytes)?;
+    let len = u32::from_le_bytes(len_bytes) as usize;
+    let mut buf = vec![0u8; len];
+    reader.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+fn read_len_prefixed_string(reader: &mut impl Read) -> Result<String> {
+    let bytes = read_len_prefixed(reader)?;
+    String::from_utf8(bytes).map_err(|e| Error::Io(io::Error::new(io::ErrorKind::InvalidData, e)))
+}
+
 /// A table containing key-value mappings
 pub struct Table<'db, 'txn, K: RedbKey + ?Sized + 'txn, V: RedbValue + ?Sized + 'txn> {
     name: String,
     transaction: &'txn WriteTransaction<'db>,
     tree: BtreeMut<'txn, K, V>,
+    // Number of entries currently in `tree`. Maintained incrementally so that `len()`/
+    // `is_empty()` are O(1) instead of walking the tree, and persisted alongside the root page
+    // by `WriteTransaction::close_table` so an aborted transaction leaves the previously
+    // committed count untouched.
+    entries: u64,
+    // Whether this table was opened with a Bloom filter; re-reported to
+    // `WriteTransaction::close_table` so the flag survives in the table's persisted metadata.
+    bloom_filter: bool,
+    // The codec applied to values before they're handed to `tree`; re-reported to
+    // `WriteTransaction::close_table` for the same reason as `bloom_filter` above.
+    compression: Compression,
+    // Keys changed by this table since it was opened, handed off to
+    // `WriteTransaction::close_table` so they reach registered watchers only if/when the
+    // transaction commits.
+    changes: Vec<TableChange>,
 }
 
 impl<'db, 'txn, K: RedbKey + ?Sized + 'txn, V: RedbValue + ?Sized + 'txn> Table<'db, 'txn, K, V> {
+    // Each table-level storage option (bloom filter, compression, ...) threads one more
+    // parameter through here from `WriteTransaction::open_table`.
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn new(
         name: &str,
         table_root: Option<(PageNumber, Checksum)>,
+        entries: u64,
+        bloom_filter: bool,
+        compression: Compression,
         freed_pages: Rc<RefCell<Vec<PageNumber>>>,
-        mem: &'db TransactionalMemory,
+        mem: TransactionalMemory,
         transaction: &'txn WriteTransaction<'db>,
     ) -> Table<'db, 'txn, K, V> {
         Table {
             name: name.to_string(),
             transaction,
-            tree: BtreeMut::new(table_root, mem, freed_pages),
+            tree: BtreeMut::new(table_root, mem, freed_pages, bloom_filter, compression),
+            entries,
+            bloom_filter,
+            compression,
+            changes: Vec::new(),
         }
     }
 
-    #[allow(dead_code)]
-    pub(crate) fn print_debug(&self, include_values: bool) -> Result {
-        self.tree.print_debug(include_values)
-    }
-
     /// Removes and returns the first key-value pair in the table
-    pub fn pop_first(&mut self) -> Result<Option<(AccessGuard<K>, AccessGuard<V>)>> {
+    pub fn pop_first(&mut self) -> Result<Option<(AccessGuard<'_, K>, AccessGuard<'_, V>)>> {
         // TODO: optimize this
         let first = self.iter()?.next();
         if let Some((ref key, _)) = first {
@@ -54,9 +98,9 @@ impl<'db, 'txn, K: RedbKey + ?Sized + 'txn, V: RedbValue + ?Sized + 'txn> Table<
     }
 
     /// Removes and returns the last key-value pair in the table
-    pub fn pop_last(&mut self) -> Result<Option<(AccessGuard<K>, AccessGuard<V>)>> {
+    pub fn pop_last(&mut self) -> Result<Option<(AccessGuard<'_, K>, AccessGuard<'_, V>)>> {
         // TODO: optimize this
-        let first = self.iter()?.rev().next();
+        let first = self.iter()?.next_back();
         if let Some((ref key, _)) = first {
             let owned_key = K::as_bytes(key.value().borrow()).as_ref().to_vec();
             drop(first);
@@ -76,12 +120,24 @@ impl<'db, 'txn, K: RedbKey + ?Sized + 'txn, V: RedbValue + ?Sized + 'txn> Table<
     where
         K: 'a,
         // TODO: we should not require Clone here
-        KR: Borrow<K::SelfType<'a>> + ?Sized + Clone + 'a,
+        KR: Borrow<K::SelfType<'a>> + Clone + 'a,
     {
+        let entries = &mut self.entries;
+        let changes = &mut self.changes;
         // Safety: No other references to this table can exist.
         // Tables can only be opened mutably in one location (see Error::TableAlreadyOpen),
         // and we borrow &mut self.
-        unsafe { self.tree.drain(range).map(Drain::new) }
+        unsafe {
+            self.tree
+                .drain(range, |key| {
+                    *entries -= 1;
+                    changes.push(TableChange {
+                        key: key.to_vec(),
+                        kind: ChangeKind::Removal,
+                    });
+                })
+                .map(Drain::new)
+        }
     }
 
     /// Insert mapping of the given key to the given value
@@ -91,32 +147,51 @@ impl<'db, 'txn, K: RedbKey + ?Sized + 'txn, V: RedbValue + ?Sized + 'txn> Table<
         &mut self,
         key: impl Borrow<K::SelfType<'a>>,
         value: impl Borrow<V::SelfType<'a>>,
-    ) -> Result<Option<AccessGuard<V>>>
+    ) -> Result<Option<AccessGuard<'_, V>>>
     where
         K: 'a,
         V: 'a,
     {
-        // Safety: No other references to this table can exist.
-        // Tables can only be opened mutably in one location (see Error::TableAlreadyOpen),
-        // and we borrow &mut self.
-        unsafe { self.tree.insert(key.borrow(), value.borrow()) }
+        let key_bytes = K::as_bytes(key.borrow()).as_ref().to_vec();
+        let result = self.tree.insert(key.borrow(), value.borrow())?;
+        let kind = if result.is_none() {
+            self.entries += 1;
+            ChangeKind::Insert
+        } else {
+            ChangeKind::Overwrite
+        };
+        self.changes.push(TableChange { key: key_bytes, kind });
+        Ok(result)
     }
 
     /// Reserve space to insert a key-value pair
     /// The returned reference will have length equal to value_length
+    ///
+    /// Returns [`Error::InsertReserveNotSupported`] if this table was opened with
+    /// [`crate::TableDefinition::with_compression`], since the reserved length is the
+    /// pre-compression length and can't be guaranteed to match what's actually stored.
     // TODO: return type should be V, not [u8]
     pub fn insert_reserve<'a>(
         &mut self,
         key: impl Borrow<K::SelfType<'a>>,
         value_length: usize,
-    ) -> Result<AccessGuardMut<K, &[u8]>>
+    ) -> Result<AccessGuardMut<'_, K>>
     where
         K: 'a,
     {
-        // Safety: No other references to this table can exist.
-        // Tables can only be opened mutably in one location (see Error::TableAlreadyOpen),
-        // and we borrow &mut self.
-        unsafe { self.tree.insert_reserve(key.borrow(), value_length) }
+        if self.compression.is_enabled() {
+            return Err(Error::InsertReserveNotSupported(self.name.clone()));
+        }
+        let key_bytes = K::as_bytes(key.borrow()).as_ref().to_vec();
+        let (guard, was_new) = self.tree.insert_reserve(key.borrow(), value_length)?;
+        let kind = if was_new {
+            self.entries += 1;
+            ChangeKind::Insert
+        } else {
+            ChangeKind::Overwrite
+        };
+        self.changes.push(TableChange { key: key_bytes, kind });
+        Ok(guard)
     }
 
     /// Removes the given key
@@ -125,27 +200,99 @@ impl<'db, 'txn, K: RedbKey + ?Sized + 'txn, V: RedbValue + ?Sized + 'txn> Table<
     pub fn remove<'a>(
         &mut self,
         key: impl Borrow<K::SelfType<'a>>,
-    ) -> Result<Option<AccessGuard<V>>>
+    ) -> Result<Option<AccessGuard<'_, V>>>
     where
         K: 'a,
     {
-        // Safety: No other references to this table can exist.
-        // Tables can only be opened mutably in one location (see Error::TableAlreadyOpen),
-        // and we borrow &mut self.
-        unsafe { self.tree.remove(key.borrow()) }
+        let key_bytes = K::as_bytes(key.borrow()).as_ref().to_vec();
+        let result = self.tree.remove(key.borrow())?;
+        if result.is_some() {
+            self.entries -= 1;
+            self.changes.push(TableChange {
+                key: key_bytes,
+                kind: ChangeKind::Removal,
+            });
+        }
+        Ok(result)
+    }
+
+    /// Bulk-loads a stream produced by [`ReadableTable::export`], returning the number of
+    /// entries loaded
+    ///
+    /// Because the stream is already sorted by key, this builds the tree bottom-up (filling
+    /// leaves directly, then building internal levels from them) instead of performing one
+    /// `insert` per entry, which is dramatically faster for large tables.
+    ///
+    /// Returns [`Error::ImportRequiresEmptyTable`] if this table already has entries, and
+    /// [`Error::TableTypeMismatch`] if the stream's key/value types don't match this table's.
+    pub fn import(&mut self, mut reader: impl Read) -> Result<u64> {
+        if self.entries != 0 {
+            return Err(Error::ImportRequiresEmptyTable(self.name.clone()));
+        }
+
+        let mut magic = [0u8; EXPORT_MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+        if magic != *EXPORT_MAGIC {
+            return Err(Error::Io(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a redb export stream",
+            )));
+        }
+
+        let key_type = read_len_prefixed_string(&mut reader)?;
+        let value_type = read_len_prefixed_string(&mut reader)?;
+        if key_type != K::redb_type_name() || value_type != V::redb_type_name() {
+            return Err(Error::TableTypeMismatch(format!(
+                "export stream has key={}, value={}; importing into table '{}' with key={}, value={}",
+                key_type,
+                value_type,
+                self.name,
+                K::redb_type_name(),
+                V::redb_type_name()
+            )));
+        }
+
+        let mut count_bytes = [0u8; 8];
+        reader.read_exact(&mut count_bytes)?;
+        let count = u64::from_le_bytes(count_bytes);
+
+        let mut entries = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let key = read_len_prefixed(&mut reader)?;
+            let value = read_len_prefixed(&mut reader)?;
+            entries.push((key, value));
+        }
+
+        // `bulk_load` builds leaves directly from this order, so the stream must already be
+        // sorted in strictly increasing key order (no duplicates); a hand-rolled or corrupted
+        // export could violate that, silently breaking every lookup that binary-searches on it.
+        if !entries.windows(2).all(|w| K::compare(&w[0].0, &w[1].0) == std::cmp::Ordering::Less) {
+            return Err(Error::ImportNotSorted(self.name.clone()));
+        }
+
+        let imported = self.tree.bulk_load(entries)?;
+        self.entries = imported;
+        Ok(imported)
     }
 }
 
 impl<'db, 'txn, K: RedbKey + ?Sized, V: RedbValue + ?Sized> ReadableTable<K, V>
     for Table<'db, 'txn, K, V>
 {
-    fn get<'a>(&self, key: impl Borrow<K::SelfType<'a>>) -> Result<Option<AccessGuard<V>>>
+    fn get<'a>(&self, key: impl Borrow<K::SelfType<'a>>) -> Result<Option<AccessGuard<'_, V>>>
     where
         K: 'a,
     {
         self.tree.get(key.borrow())
     }
 
+    fn contains_key<'a>(&self, key: impl Borrow<K::SelfType<'a>>) -> Result<bool>
+    where
+        K: 'a,
+    {
+        self.tree.contains_key(key.borrow())
+    }
+
     fn range<'a, KR>(&'a self, range: impl RangeBounds<KR> + 'a) -> Result<RangeIter<'a, K, V>>
     where
         K: 'a,
@@ -155,23 +302,54 @@ impl<'db, 'txn, K: RedbKey + ?Sized, V: RedbValue + ?Sized> ReadableTable<K, V>
     }
 
     fn len(&self) -> Result<usize> {
-        self.tree.len()
+        Ok(self.entries as usize)
     }
 
     fn is_empty(&self) -> Result<bool> {
         self.len().map(|x| x == 0)
     }
+
+    fn rank<'a>(&self, key: impl Borrow<K::SelfType<'a>>) -> Result<u64>
+    where
+        K: 'a,
+    {
+        self.tree.rank(key.borrow())
+    }
+
+    fn len_range<'a, KR>(&'a self, range: impl RangeBounds<KR> + 'a) -> Result<u64>
+    where
+        K: 'a,
+        KR: Borrow<K::SelfType<'a>> + ?Sized + 'a,
+    {
+        self.tree.len_range(range)
+    }
 }
 
 impl<'db, 'txn, K: RedbKey + ?Sized, V: RedbValue + ?Sized> Drop for Table<'db, 'txn, K, V> {
     fn drop(&mut self) {
-        self.transaction.close_table(&self.name, &mut self.tree);
+        self.transaction.close_table(
+            &self.name,
+            &mut self.tree,
+            self.entries,
+            self.bloom_filter,
+            self.compression,
+            std::mem::take(&mut self.changes),
+        );
     }
 }
 
 pub trait ReadableTable<K: RedbKey + ?Sized, V: RedbValue + ?Sized> {
     /// Returns the value corresponding to the given key
-    fn get<'a>(&self, key: impl Borrow<K::SelfType<'a>>) -> Result<Option<AccessGuard<V>>>
+    fn get<'a>(&self, key: impl Borrow<K::SelfType<'a>>) -> Result<Option<AccessGuard<'_, V>>>
+    where
+        K: 'a;
+
+    /// Returns `true` if the table contains the given key
+    ///
+    /// Equivalent to `self.get(key)?.is_some()`, but on a table created with
+    /// [`TableDefinition::with_bloom_filter`] this can answer "definitely absent" without
+    /// reading the candidate leaf's entries.
+    fn contains_key<'a>(&self, key: impl Borrow<K::SelfType<'a>>) -> Result<bool>
     where
         K: 'a;
 
@@ -185,10 +363,10 @@ pub trait ReadableTable<K: RedbKey + ?Sized, V: RedbValue + ?Sized> {
     /// # use tempfile::NamedTempFile;
     /// const TABLE: TableDefinition<&str, u64> = TableDefinition::new("my_data");
     ///
-    /// # fn main() -> Result<(), Error> {
+    /// # fn main() -> Result<()> {
     /// # let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
     /// # let filename = tmpfile.path();
-    /// let db = unsafe { Database::create(filename)? };
+    /// let db = Database::create(filename)?;
     /// let write_txn = db.begin_write()?;
     /// {
     ///     let mut table = write_txn.open_table(TABLE)?;
@@ -219,24 +397,63 @@ pub trait ReadableTable<K: RedbKey + ?Sized, V: RedbValue + ?Sized> {
     fn is_empty(&self) -> Result<bool>;
 
     /// Returns a double-ended iterator over all elements in the table
-    fn iter(&self) -> Result<RangeIter<K, V>> {
+    fn iter(&self) -> Result<RangeIter<'_, K, V>> {
         self.range::<K::SelfType<'_>>(..)
     }
+
+    /// Streams every key-value pair in the table to `writer` in a self-describing, sorted,
+    /// length-delimited format (magic header + `K`/`V`'s [`RedbValue::redb_type_name`] +
+    /// `u64` entry count + length-prefixed records), independent of the on-disk storage format, for backup or
+    /// migration across redb versions. Pairs with [`Table::import`].
+    fn export(&self, mut writer: impl Write) -> Result<()> {
+        writer.write_all(EXPORT_MAGIC)?;
+        write_len_prefixed(&mut writer, K::redb_type_name().as_bytes())?;
+        write_len_prefixed(&mut writer, V::redb_type_name().as_bytes())?;
+        writer.write_all(&(self.len()? as u64).to_le_bytes())?;
+        for (k, v) in self.iter()? {
+            write_len_prefixed(&mut writer, K::as_bytes(k.value().borrow()).as_ref())?;
+            write_len_prefixed(&mut writer, V::as_bytes(v.value().borrow()).as_ref())?;
+        }
+        Ok(())
+    }
+
+    /// Returns the number of keys in the table strictly less than `key`
+    ///
+    /// Computed in O(log n) via the subtree entry counts maintained on each internal node,
+    /// rather than by counting a range.
+    fn rank<'a>(&self, key: impl Borrow<K::SelfType<'a>>) -> Result<u64>
+    where
+        K: 'a;
+
+    /// Returns the number of keys within `range`
+    ///
+    /// Computed in O(log n), like [`Self::rank`], as the difference between the number of keys
+    /// before the range's end and before its start, each counted from the subtree counts along
+    /// a root-to-leaf descent rather than by iterating the range.
+    fn len_range<'a, KR>(&'a self, range: impl RangeBounds<KR> + 'a) -> Result<u64>
+    where
+        K: 'a,
+        KR: Borrow<K::SelfType<'a>> + ?Sized + 'a;
 }
 
 /// A read-only table
 pub struct ReadOnlyTable<'txn, K: RedbKey + ?Sized, V: RedbValue + ?Sized> {
     tree: Btree<'txn, K, V>,
+    entries: u64,
 }
 
 impl<'txn, K: RedbKey + ?Sized, V: RedbValue + ?Sized> ReadOnlyTable<'txn, K, V> {
     pub(crate) fn new(
         root_page: Option<(PageNumber, Checksum)>,
+        entries: u64,
+        #[allow(unused_variables)] bloom_filter: bool,
+        compression: Compression,
         hint: PageHint,
-        mem: &'txn TransactionalMemory,
+        mem: TransactionalMemory,
     ) -> ReadOnlyTable<'txn, K, V> {
         ReadOnlyTable {
-            tree: Btree::new(root_page, hint, mem),
+            tree: Btree::new(root_page, hint, mem, compression),
+            entries,
         }
     }
 }
@@ -244,13 +461,20 @@ impl<'txn, K: RedbKey + ?Sized, V: RedbValue + ?Sized> ReadOnlyTable<'txn, K, V>
 impl<'txn, K: RedbKey + ?Sized, V: RedbValue + ?Sized> ReadableTable<K, V>
     for ReadOnlyTable<'txn, K, V>
 {
-    fn get<'a>(&self, key: impl Borrow<K::SelfType<'a>>) -> Result<Option<AccessGuard<V>>>
+    fn get<'a>(&self, key: impl Borrow<K::SelfType<'a>>) -> Result<Option<AccessGuard<'_, V>>>
     where
         K: 'a,
     {
         self.tree.get(key.borrow())
     }
 
+    fn contains_key<'a>(&self, key: impl Borrow<K::SelfType<'a>>) -> Result<bool>
+    where
+        K: 'a,
+    {
+        self.tree.contains_key(key.borrow())
+    }
+
     fn range<'a, KR>(&'a self, range: impl RangeBounds<KR> + 'a) -> Result<RangeIter<'a, K, V>>
     where
         K: 'a,
@@ -260,14 +484,32 @@ impl<'txn, K: RedbKey + ?Sized, V: RedbValue + ?Sized> ReadableTable<K, V>
     }
 
     fn len(&self) -> Result<usize> {
-        self.tree.len()
+        Ok(self.entries as usize)
     }
 
     fn is_empty(&self) -> Result<bool> {
         self.len().map(|x| x == 0)
     }
+
+    fn rank<'a>(&self, key: impl Borrow<K::SelfType<'a>>) -> Result<u64>
+    where
+        K: 'a,
+    {
+        self.tree.rank(key.borrow())
+    }
+
+    fn len_range<'a, KR>(&'a self, range: impl RangeBounds<KR> + 'a) -> Result<u64>
+    where
+        K: 'a,
+        KR: Borrow<K::SelfType<'a>> + ?Sized + 'a,
+    {
+        self.tree.len_range(range)
+    }
 }
 
+// `Table::drain` removes the whole range (and updates `entries`/`changes` for it) up front, via
+// `BtreeMut::drain`'s `on_remove` callback, so this is just a thin wrapper: even a `Drain`
+// dropped before being fully consumed leaves the owning `Table`'s bookkeeping correct.
 pub struct Drain<'a, K: RedbKey + ?Sized + 'a, V: RedbValue + ?Sized + 'a> {
     inner: BtreeDrain<'a, K, V>,
 }
@@ -282,11 +524,7 @@ impl<'a, K: RedbKey + ?Sized + 'a, V: RedbValue + ?Sized + 'a> Iterator for Drai
     type Item = (AccessGuard<'a, K>, AccessGuard<'a, V>);
 
     fn next(&mut self) -> Option<Self::Item> {
-        let entry = self.inner.next()?;
-        let (page, key_range, value_range) = entry.into_raw();
-        let key = AccessGuard::with_page(page.clone(), key_range);
-        let value = AccessGuard::with_page(page, value_range);
-        Some((key, value))
+        self.inner.next()
     }
 }
 
@@ -294,11 +532,7 @@ impl<'a, K: RedbKey + ?Sized + 'a, V: RedbValue + ?Sized + 'a> DoubleEndedIterat
     for Drain<'a, K, V>
 {
     fn next_back(&mut self) -> Option<Self::Item> {
-        let entry = self.inner.next_back()?;
-        let (page, key_range, value_range) = entry.into_raw();
-        let key = AccessGuard::with_page(page.clone(), key_range);
-        let value = AccessGuard::with_page(page, value_range);
-        Some((key, value))
+        self.inner.next_back()
     }
 }
 
@@ -316,14 +550,7 @@ impl<'a, K: RedbKey + ?Sized + 'a, V: RedbValue + ?Sized + 'a> Iterator for Rang
     type Item = (AccessGuard<'a, K>, AccessGuard<'a, V>);
 
     fn next(&mut self) -> Option<Self::Item> {
-        if let Some(entry) = self.inner.next() {
-            let (page, key_range, value_range) = entry.into_raw();
-            let key = AccessGuard::with_page(page.clone(), key_range);
-            let value = AccessGuard::with_page(page, value_range);
-            Some((key, value))
-        } else {
-            None
-        }
+        self.inner.next()
     }
 }
 
@@ -331,21 +558,14 @@ impl<'a, K: RedbKey + ?Sized + 'a, V: RedbValue + ?Sized + 'a> DoubleEndedIterat
     for RangeIter<'a, K, V>
 {
     fn next_back(&mut self) -> Option<Self::Item> {
-        if let Some(entry) = self.inner.next_back() {
-            let (page, key_range, value_range) = entry.into_raw();
-            let key = AccessGuard::with_page(page.clone(), key_range);
-            let value = AccessGuard::with_page(page, value_range);
-            Some((key, value))
-        } else {
-            None
-        }
+        self.inner.next_back()
     }
 }
 
 #[cfg(test)]
 mod test {
     use crate::types::{RedbKey, RedbValue, Sealed};
-    use crate::{Database, ReadableTable, TableDefinition};
+    use crate::{Database, Error, ReadableTable, TableDefinition};
     use std::cmp::Ordering;
     use tempfile::NamedTempFile;
 
@@ -420,4 +640,453 @@ mod test {
         }
         assert!(iter.next().is_none());
     }
+
+    #[test]
+    fn len_persists_across_commits_and_ignores_aborted_writes() {
+        const TABLE: TableDefinition<&str, u64> = TableDefinition::new("counts");
+
+        let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+        let db = Database::create(tmpfile.path()).unwrap();
+
+        let write_txn = db.begin_write().unwrap();
+        {
+            let mut table = write_txn.open_table(TABLE).unwrap();
+            table.insert("a", &0).unwrap();
+            table.insert("b", &1).unwrap();
+            assert_eq!(table.len().unwrap(), 2);
+        }
+        write_txn.commit().unwrap();
+
+        let read_txn = db.begin_read().unwrap();
+        let table = read_txn.open_table(TABLE).unwrap();
+        assert_eq!(table.len().unwrap(), 2);
+        drop(table);
+        drop(read_txn);
+
+        // An aborted transaction's mutations must not be reflected in the persisted count.
+        let write_txn = db.begin_write().unwrap();
+        {
+            let mut table = write_txn.open_table(TABLE).unwrap();
+            table.insert("c", &2).unwrap();
+            table.remove("a").unwrap();
+            assert_eq!(table.len().unwrap(), 2);
+        }
+        write_txn.abort().unwrap();
+
+        let read_txn = db.begin_read().unwrap();
+        let table = read_txn.open_table(TABLE).unwrap();
+        assert_eq!(table.len().unwrap(), 2);
+        assert!(table.get("a").unwrap().is_some());
+        assert!(table.get("c").unwrap().is_none());
+    }
+
+    #[test]
+    fn len_accounts_for_a_dropped_unconsumed_drain() {
+        const TABLE: TableDefinition<u64, u64> = TableDefinition::new("drained");
+
+        let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+        let db = Database::create(tmpfile.path()).unwrap();
+
+        let write_txn = db.begin_write().unwrap();
+        {
+            let mut table = write_txn.open_table(TABLE).unwrap();
+            for i in 0..10u64 {
+                table.insert(&i, &i).unwrap();
+            }
+            // Dropping a `Drain` without consuming it is a normal way to delete a range; the
+            // keys must already be gone from the tree by the time this happens, so `len()`
+            // must reflect that immediately, not just once the iterator is (maybe never)
+            // consumed.
+            table.drain(0u64..5u64).unwrap();
+            assert_eq!(table.len().unwrap(), 5);
+            let remaining: Vec<u64> = table.iter().unwrap().map(|(k, _)| k.value()).collect();
+            assert_eq!(remaining, vec![5, 6, 7, 8, 9]);
+        }
+        write_txn.commit().unwrap();
+
+        let read_txn = db.begin_read().unwrap();
+        let table = read_txn.open_table(TABLE).unwrap();
+        assert_eq!(table.len().unwrap(), 5);
+    }
+
+    #[test]
+    fn insert_reserve_only_increments_len_for_a_new_key() {
+        const TABLE: TableDefinition<&str, &[u8]> = TableDefinition::new("reserved");
+
+        let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+        let db = Database::create(tmpfile.path()).unwrap();
+
+        let write_txn = db.begin_write().unwrap();
+        {
+            let mut table = write_txn.open_table(TABLE).unwrap();
+            table.insert("a", [1u8].as_slice()).unwrap();
+            assert_eq!(table.len().unwrap(), 1);
+            // Overwriting the same key via insert_reserve must not bump the count.
+            let mut guard = table.insert_reserve("a", 1).unwrap();
+            guard.as_mut().copy_from_slice(&[2u8]);
+            drop(guard);
+            assert_eq!(table.len().unwrap(), 1);
+        }
+        write_txn.commit().unwrap();
+
+        let read_txn = db.begin_read().unwrap();
+        let table = read_txn.open_table(TABLE).unwrap();
+        assert_eq!(table.len().unwrap(), 1);
+    }
+
+    #[test]
+    fn insert_reserve_writes_into_the_correct_leaf_once_the_root_has_split() {
+        const TABLE: TableDefinition<u64, &[u8]> = TableDefinition::new("reserved_split");
+
+        let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+        let db = Database::create(tmpfile.path()).unwrap();
+
+        let write_txn = db.begin_write().unwrap();
+        {
+            let mut table = write_txn.open_table(TABLE).unwrap();
+            // Enough entries to force the root to become an internal node, so the reserved
+            // key's leaf is not the root itself.
+            for i in 0..20u64 {
+                table.insert(&i, [0u8; 4].as_slice()).unwrap();
+            }
+            let mut guard = table.insert_reserve(&999u64, 4).unwrap();
+            guard.as_mut().copy_from_slice(&[1, 2, 3, 4]);
+            drop(guard);
+        }
+        write_txn.commit().unwrap();
+
+        let read_txn = db.begin_read().unwrap();
+        let table = read_txn.open_table(TABLE).unwrap();
+        assert_eq!(table.get(&999u64).unwrap().unwrap().value(), &[1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn bloom_filter_contains_key() {
+        const TABLE: TableDefinition<&str, u64> = TableDefinition::new("bloom").with_bloom_filter(true);
+
+        let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+        let db = Database::create(tmpfile.path()).unwrap();
+
+        let write_txn = db.begin_write().unwrap();
+        {
+            let mut table = write_txn.open_table(TABLE).unwrap();
+            for i in 0..100u64 {
+                table.insert(i.to_string().as_str(), &i).unwrap();
+            }
+        }
+        write_txn.commit().unwrap();
+
+        let read_txn = db.begin_read().unwrap();
+        let table = read_txn.open_table(TABLE).unwrap();
+        for i in 0..100u64 {
+            assert!(table.contains_key(i.to_string().as_str()).unwrap());
+        }
+        assert!(!table.contains_key("not present").unwrap());
+    }
+
+    #[test]
+    fn rank_and_len_range() {
+        const TABLE: TableDefinition<u64, u64> = TableDefinition::new("ranked");
+
+        let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+        let db = Database::create(tmpfile.path()).unwrap();
+
+        let write_txn = db.begin_write().unwrap();
+        {
+            let mut table = write_txn.open_table(TABLE).unwrap();
+            // Insert enough entries to force several splits, so internal nodes' subtree counts
+            // are actually exercised, not just a single leaf's.
+            for i in 0..200u64 {
+                table.insert(&i, &i).unwrap();
+            }
+        }
+        write_txn.commit().unwrap();
+
+        let read_txn = db.begin_read().unwrap();
+        let table = read_txn.open_table(TABLE).unwrap();
+
+        assert_eq!(table.rank(&0u64).unwrap(), 0);
+        assert_eq!(table.rank(&50u64).unwrap(), 50);
+        assert_eq!(table.rank(&200u64).unwrap(), 200);
+
+        assert_eq!(table.len_range::<u64>(..).unwrap(), 200);
+        assert_eq!(table.len_range(10u64..20u64).unwrap(), 10);
+        assert_eq!(table.len_range(10u64..=20u64).unwrap(), 11);
+        assert_eq!(table.len_range(..50u64).unwrap(), 50);
+        assert_eq!(table.len_range(190u64..).unwrap(), 10);
+        assert_eq!(table.len_range(500u64..600u64).unwrap(), 0);
+    }
+
+    #[test]
+    fn export_import_roundtrip() {
+        const SRC: TableDefinition<u64, &str> = TableDefinition::new("src");
+        const DST: TableDefinition<u64, &str> = TableDefinition::new("dst");
+
+        let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+        let db = Database::create(tmpfile.path()).unwrap();
+
+        let write_txn = db.begin_write().unwrap();
+        {
+            let mut table = write_txn.open_table(SRC).unwrap();
+            for i in 0..50u64 {
+                table.insert(&i, "value").unwrap();
+            }
+        }
+        write_txn.commit().unwrap();
+
+        let mut stream = Vec::new();
+        let read_txn = db.begin_read().unwrap();
+        let table = read_txn.open_table(SRC).unwrap();
+        table.export(&mut stream).unwrap();
+        drop(table);
+        drop(read_txn);
+
+        let write_txn = db.begin_write().unwrap();
+        {
+            let mut table = write_txn.open_table(DST).unwrap();
+            let imported = table.import(stream.as_slice()).unwrap();
+            assert_eq!(imported, 50);
+            assert_eq!(table.len().unwrap(), 50);
+        }
+        write_txn.commit().unwrap();
+
+        let read_txn = db.begin_read().unwrap();
+        let table = read_txn.open_table(DST).unwrap();
+        for i in 0..50u64 {
+            assert_eq!(table.get(&i).unwrap().unwrap().value(), "value");
+        }
+        assert_eq!(table.len().unwrap(), 50);
+
+        // Importing into a non-empty table is rejected.
+        let write_txn = db.begin_write().unwrap();
+        {
+            let mut table = write_txn.open_table(DST).unwrap();
+            assert!(matches!(
+                table.import(stream.as_slice()),
+                Err(Error::ImportRequiresEmptyTable(_))
+            ));
+        }
+        write_txn.abort().unwrap();
+    }
+
+    #[test]
+    fn import_rejects_an_unsorted_stream() {
+        const TABLE: TableDefinition<u64, &str> = TableDefinition::new("unsorted_dst");
+
+        // Hand-build a valid-looking but unsorted stream (keys 5, 1, 3) the way a hand-rolled
+        // or corrupted export might, to make sure `import` catches this rather than silently
+        // building a tree that breaks every lookup relying on sorted order.
+        let mut stream = Vec::new();
+        stream.extend_from_slice(b"redbxp01");
+        let key_type = u64::redb_type_name();
+        let value_type = <&str>::redb_type_name();
+        stream.extend_from_slice(&(key_type.len() as u32).to_le_bytes());
+        stream.extend_from_slice(key_type.as_bytes());
+        stream.extend_from_slice(&(value_type.len() as u32).to_le_bytes());
+        stream.extend_from_slice(value_type.as_bytes());
+        stream.extend_from_slice(&3u64.to_le_bytes());
+        for (key, value) in [(5u64, "a"), (1u64, "b"), (3u64, "c")] {
+            let key_bytes = key.to_be_bytes();
+            stream.extend_from_slice(&(key_bytes.len() as u32).to_le_bytes());
+            stream.extend_from_slice(&key_bytes);
+            stream.extend_from_slice(&(value.len() as u32).to_le_bytes());
+            stream.extend_from_slice(value.as_bytes());
+        }
+
+        let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+        let db = Database::create(tmpfile.path()).unwrap();
+        let write_txn = db.begin_write().unwrap();
+        {
+            let mut table = write_txn.open_table(TABLE).unwrap();
+            assert!(matches!(
+                table.import(stream.as_slice()),
+                Err(Error::ImportNotSorted(_))
+            ));
+            assert_eq!(table.len().unwrap(), 0);
+        }
+        write_txn.abort().unwrap();
+    }
+
+    #[test]
+    fn watch_notifies_only_on_commit() {
+        use crate::ChangeKind;
+        use std::cell::RefCell as StdRefCell;
+        use std::rc::Rc as StdRc;
+
+        const TABLE: TableDefinition<&str, u64> = TableDefinition::new("watched");
+
+        let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+        let db = Database::create(tmpfile.path()).unwrap();
+
+        let seen: StdRc<StdRefCell<Vec<(String, ChangeKind)>>> = StdRc::new(StdRefCell::new(Vec::new()));
+        let seen_clone = seen.clone();
+        db.watch(TABLE.name(), move |changes| {
+            for change in changes {
+                seen_clone
+                    .borrow_mut()
+                    .push((String::from_utf8(change.key.clone()).unwrap(), change.kind));
+            }
+        });
+        let receiver = db.watch_channel(TABLE.name());
+
+        // An aborted transaction must not notify either watcher.
+        let write_txn = db.begin_write().unwrap();
+        {
+            let mut table = write_txn.open_table(TABLE).unwrap();
+            table.insert("a", &1).unwrap();
+        }
+        write_txn.abort().unwrap();
+        assert!(seen.borrow().is_empty());
+        assert!(receiver.try_recv().is_err());
+
+        let write_txn = db.begin_write().unwrap();
+        {
+            let mut table = write_txn.open_table(TABLE).unwrap();
+            table.insert("a", &1).unwrap();
+            table.insert("a", &2).unwrap();
+            table.remove("missing").unwrap();
+        }
+        write_txn.commit().unwrap();
+
+        assert_eq!(
+            *seen.borrow(),
+            vec![
+                ("a".to_string(), ChangeKind::Insert),
+                ("a".to_string(), ChangeKind::Overwrite),
+            ]
+        );
+        let via_channel = receiver.try_recv().unwrap();
+        assert_eq!(via_channel.len(), 2);
+        assert_eq!(via_channel[0].kind, ChangeKind::Insert);
+        assert_eq!(via_channel[1].kind, ChangeKind::Overwrite);
+    }
+
+    #[test]
+    fn watch_notifies_on_insert_reserve() {
+        use crate::ChangeKind;
+
+        const TABLE: TableDefinition<&str, &[u8]> = TableDefinition::new("watched_reserve");
+
+        let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+        let db = Database::create(tmpfile.path()).unwrap();
+
+        let receiver = db.watch_channel(TABLE.name());
+
+        let write_txn = db.begin_write().unwrap();
+        {
+            let mut table = write_txn.open_table(TABLE).unwrap();
+            let mut guard = table.insert_reserve("a", 1).unwrap();
+            guard.as_mut().copy_from_slice(&[1]);
+            drop(guard);
+            let mut guard = table.insert_reserve("a", 1).unwrap();
+            guard.as_mut().copy_from_slice(&[2]);
+            drop(guard);
+        }
+        write_txn.commit().unwrap();
+
+        let changes = receiver.try_recv().unwrap();
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].kind, ChangeKind::Insert);
+        assert_eq!(changes[1].kind, ChangeKind::Overwrite);
+    }
+
+    #[test]
+    fn watch_callback_may_commit_its_own_transaction() {
+        // A callback that reacts to a commit by starting and committing its own write
+        // transaction on the same `Database` (e.g. an index maintained on top of redb) must
+        // not panic by re-entering an already-borrowed `RefCell`.
+        const SOURCE: TableDefinition<&str, u64> = TableDefinition::new("source");
+        const INDEX: TableDefinition<&str, u64> = TableDefinition::new("index");
+
+        let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+        let db = Database::create(tmpfile.path()).unwrap();
+        let db = std::rc::Rc::new(db);
+
+        {
+            let write_txn = db.begin_write().unwrap();
+            write_txn.open_table(INDEX).unwrap();
+            write_txn.commit().unwrap();
+        }
+
+        let db_clone = db.clone();
+        db.watch(SOURCE.name(), move |changes| {
+            let write_txn = db_clone.begin_write().unwrap();
+            {
+                let mut index = write_txn.open_table(INDEX).unwrap();
+                for change in changes {
+                    index
+                        .insert(std::str::from_utf8(&change.key).unwrap(), &1)
+                        .unwrap();
+                }
+            }
+            write_txn.commit().unwrap();
+        });
+
+        let write_txn = db.begin_write().unwrap();
+        {
+            let mut table = write_txn.open_table(SOURCE).unwrap();
+            table.insert("a", &1).unwrap();
+        }
+        write_txn.commit().unwrap();
+
+        let read_txn = db.begin_read().unwrap();
+        let index = read_txn.open_table(INDEX).unwrap();
+        assert_eq!(index.len().unwrap(), 1);
+        assert!(index.get("a").unwrap().is_some());
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn lz4_compression_roundtrip_and_rejects_insert_reserve() {
+        use crate::{Compression, Error};
+
+        const TABLE: TableDefinition<&str, &[u8]> =
+            TableDefinition::new("compressed").with_compression(Compression::Lz4);
+
+        let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+        let db = Database::create(tmpfile.path()).unwrap();
+
+        let write_txn = db.begin_write().unwrap();
+        {
+            let mut table = write_txn.open_table(TABLE).unwrap();
+            let large_value = vec![b'x'; 4096];
+            table.insert("a", large_value.as_slice()).unwrap();
+            table.insert("empty", [].as_slice()).unwrap();
+
+            assert!(matches!(
+                table.insert_reserve("b", 10),
+                Err(Error::InsertReserveNotSupported(_))
+            ));
+        }
+        write_txn.commit().unwrap();
+
+        let read_txn = db.begin_read().unwrap();
+        let table = read_txn.open_table(TABLE).unwrap();
+        assert_eq!(table.get("a").unwrap().unwrap().value(), vec![b'x'; 4096].as_slice());
+        assert_eq!(table.get("empty").unwrap().unwrap().value(), [].as_slice() as &[u8]);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn zstd_compression_roundtrip() {
+        use crate::Compression;
+
+        const TABLE: TableDefinition<&str, &[u8]> =
+            TableDefinition::new("zstd_compressed").with_compression(Compression::Zstd);
+
+        let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+        let db = Database::create(tmpfile.path()).unwrap();
+
+        let write_txn = db.begin_write().unwrap();
+        {
+            let mut table = write_txn.open_table(TABLE).unwrap();
+            let large_value = vec![b'y'; 4096];
+            table.insert("a", large_value.as_slice()).unwrap();
+        }
+        write_txn.commit().unwrap();
+
+        let read_txn = db.begin_read().unwrap();
+        let table = read_txn.open_table(TABLE).unwrap();
+        assert_eq!(table.get("a").unwrap().unwrap().value(), vec![b'y'; 4096].as_slice());
+    }
 }
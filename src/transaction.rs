@@ -0,0 +1,257 @@
+use crate::table::{ReadOnlyTable, Table};
+use crate::tree_store::{Checksum, PageHint, PageNumber, TransactionalMemory};
+use crate::types::{RedbKey, RedbValue};
+use crate::watch::TableChange;
+use crate::{Compression, Database, Error, Result};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::marker::PhantomData;
+use std::rc::Rc;
+
+/// The definition of a table: its name, key/value types, and storage configuration
+///
+/// `const`-constructible, so tables are typically declared as top-level constants and reused
+/// across transactions.
+pub struct TableDefinition<'a, K: RedbKey + ?Sized + 'static, V: RedbValue + ?Sized + 'static> {
+    name: &'a str,
+    bloom_filter: bool,
+    compression: Compression,
+    _marker: PhantomData<fn() -> (&'static K, &'static V)>,
+}
+
+// Manually implemented so that `TableDefinition` is `Copy`/`Clone` regardless of whether `K`
+// and `V` are, matching its use as a `const` table handle.
+impl<'a, K: RedbKey + ?Sized + 'static, V: RedbValue + ?Sized + 'static> Copy for TableDefinition<'a, K, V> {}
+
+impl<'a, K: RedbKey + ?Sized + 'static, V: RedbValue + ?Sized + 'static> Clone for TableDefinition<'a, K, V> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, K: RedbKey + ?Sized + 'static, V: RedbValue + ?Sized + 'static> std::fmt::Debug
+    for TableDefinition<'a, K, V>
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TableDefinition").field("name", &self.name).finish()
+    }
+}
+
+impl<'a, K: RedbKey + ?Sized + 'static, V: RedbValue + ?Sized + 'static> TableDefinition<'a, K, V> {
+    pub const fn new(name: &'a str) -> Self {
+        Self {
+            name,
+            bloom_filter: false,
+            compression: Compression::None,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Enables a per-leaf Bloom filter, letting `contains_key` skip reading leaves that
+    /// provably don't contain the key. Off by default, so existing tables are unaffected.
+    pub const fn with_bloom_filter(mut self, enabled: bool) -> Self {
+        self.bloom_filter = enabled;
+        self
+    }
+
+    /// Transparently compresses values with the given codec before they're written.
+    /// `insert_reserve` is not supported on a table configured this way, since the reserved
+    /// length can't be known ahead of compression. Defaults to [`Compression::None`], so
+    /// existing tables are unaffected.
+    pub const fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        self.name
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct TableMeta {
+    pub(crate) root: Option<(PageNumber, Checksum)>,
+    // Number of entries in the table. Maintained incrementally by `Table` so that `len()` is
+    // O(1) instead of walking the tree, and committed atomically alongside the root page so
+    // an aborted transaction leaves the previously committed count untouched.
+    pub(crate) entries: u64,
+    pub(crate) bloom_filter: bool,
+    pub(crate) compression: Compression,
+    pub(crate) key_type: String,
+    pub(crate) value_type: String,
+}
+
+/// A transaction that can read, but not write, tables
+pub struct ReadTransaction<'db> {
+    db: &'db Database,
+    tables: HashMap<String, TableMeta>,
+    mem: TransactionalMemory,
+}
+
+impl<'db> ReadTransaction<'db> {
+    pub(crate) fn new(db: &'db Database, tables: HashMap<String, TableMeta>, mem: TransactionalMemory) -> Self {
+        Self { db, tables, mem }
+    }
+
+    /// Opens the given table for reading, as it existed when this transaction began
+    pub fn open_table<K: RedbKey + ?Sized + 'static, V: RedbValue + ?Sized + 'static>(
+        &self,
+        definition: TableDefinition<K, V>,
+    ) -> Result<ReadOnlyTable<'_, K, V>> {
+        let _ = &self.db;
+        let meta = self.tables.get(definition.name());
+        if let Some(meta) = meta {
+            if meta.key_type != K::redb_type_name() || meta.value_type != V::redb_type_name() {
+                return Err(Error::TableTypeMismatch(format!(
+                    "table '{}' was created with key={}, value={}; reopened with key={}, value={}",
+                    definition.name(),
+                    meta.key_type,
+                    meta.value_type,
+                    K::redb_type_name(),
+                    V::redb_type_name()
+                )));
+            }
+            Ok(ReadOnlyTable::new(
+                meta.root,
+                meta.entries,
+                meta.bloom_filter,
+                meta.compression,
+                PageHint::None,
+                self.mem.clone(),
+            ))
+        } else {
+            Ok(ReadOnlyTable::new(
+                None,
+                0,
+                false,
+                Compression::None,
+                PageHint::None,
+                self.mem.clone(),
+            ))
+        }
+    }
+}
+
+/// A transaction that can read and write tables
+///
+/// Mutations are only visible to other transactions once [`Self::commit`] is called. Dropping
+/// a `WriteTransaction` without committing discards every change it made, as if it never
+/// happened.
+pub struct WriteTransaction<'db> {
+    db: &'db Database,
+    base_tables: HashMap<String, TableMeta>,
+    pending_tables: RefCell<HashMap<String, TableMeta>>,
+    // Changes made by tables opened in this transaction, keyed by table name; merged into the
+    // database's watchers only if/when this transaction commits (see `Self::commit`).
+    pending_changes: RefCell<HashMap<String, Vec<TableChange>>>,
+    open_tables: RefCell<HashSet<String>>,
+    freed_pages: Rc<RefCell<Vec<PageNumber>>>,
+    mem: TransactionalMemory,
+}
+
+impl<'db> WriteTransaction<'db> {
+    pub(crate) fn new(db: &'db Database, base_tables: HashMap<String, TableMeta>, mem: TransactionalMemory) -> Self {
+        Self {
+            db,
+            base_tables,
+            pending_tables: RefCell::new(HashMap::new()),
+            pending_changes: RefCell::new(HashMap::new()),
+            open_tables: RefCell::new(HashSet::new()),
+            freed_pages: Rc::new(RefCell::new(Vec::new())),
+            mem,
+        }
+    }
+
+    /// Opens the given table for reading and writing
+    ///
+    /// Returns [`Error::TableAlreadyOpen`] if the same table is already open (mutably or not)
+    /// elsewhere in this transaction.
+    pub fn open_table<K: RedbKey + ?Sized + 'static, V: RedbValue + ?Sized + 'static>(
+        &self,
+        definition: TableDefinition<K, V>,
+    ) -> Result<Table<'_, '_, K, V>> {
+        let name = definition.name().to_string();
+        if !self.open_tables.borrow_mut().insert(name.clone()) {
+            return Err(Error::TableAlreadyOpen(name));
+        }
+
+        let meta = {
+            let mut pending = self.pending_tables.borrow_mut();
+            pending
+                .entry(name.clone())
+                .or_insert_with(|| {
+                    self.base_tables.get(&name).cloned().unwrap_or_else(|| TableMeta {
+                        root: None,
+                        entries: 0,
+                        bloom_filter: definition.bloom_filter,
+                        compression: definition.compression,
+                        key_type: K::redb_type_name(),
+                        value_type: V::redb_type_name(),
+                    })
+                })
+                .clone()
+        };
+
+        if meta.key_type != K::redb_type_name() || meta.value_type != V::redb_type_name() {
+            self.open_tables.borrow_mut().remove(&name);
+            return Err(Error::TableTypeMismatch(format!(
+                "table '{}' was created with key={}, value={}; reopened with key={}, value={}",
+                name, meta.key_type, meta.value_type, K::redb_type_name(), V::redb_type_name()
+            )));
+        }
+
+        Ok(Table::new(
+            &name,
+            meta.root,
+            meta.entries,
+            meta.bloom_filter,
+            meta.compression,
+            self.freed_pages.clone(),
+            self.mem.clone(),
+            self,
+        ))
+    }
+
+    pub(crate) fn close_table<K: RedbKey + ?Sized, V: RedbValue + ?Sized>(
+        &self,
+        name: &str,
+        tree: &mut crate::tree_store::BtreeMut<K, V>,
+        entries: u64,
+        bloom_filter: bool,
+        compression: Compression,
+        changes: Vec<TableChange>,
+    ) {
+        self.open_tables.borrow_mut().remove(name);
+        self.pending_tables.borrow_mut().insert(
+            name.to_string(),
+            TableMeta {
+                root: tree.get_root(),
+                entries,
+                bloom_filter,
+                compression,
+                key_type: K::redb_type_name(),
+                value_type: V::redb_type_name(),
+            },
+        );
+        if !changes.is_empty() {
+            self.pending_changes
+                .borrow_mut()
+                .entry(name.to_string())
+                .or_default()
+                .extend(changes);
+        }
+    }
+
+    /// Commits this transaction, making its changes visible to future transactions and
+    /// dispatching any registered watchers for the tables it changed
+    pub fn commit(self) -> Result<()> {
+        self.db
+            .apply_commit(self.pending_tables.into_inner(), self.pending_changes.into_inner());
+        Ok(())
+    }
+
+    /// Discards every change made by this transaction; equivalent to dropping it
+    pub fn abort(self) -> Result<()> {
+        Ok(())
+    }
+}
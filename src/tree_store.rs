@@ -0,0 +1,1080 @@
+use crate::types::RedbKey;
+use crate::{Compression, Result};
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::ops::{Bound, RangeBounds};
+use std::rc::Rc;
+
+/// Maximum number of entries held directly in a leaf before it splits
+const MAX_LEAF_ENTRIES: usize = 8;
+/// Maximum number of children held by an internal node before it splits
+const MAX_CHILDREN: usize = 8;
+/// Default bits-per-key for a leaf's Bloom filter
+const DEFAULT_BITS_PER_KEY: usize = 10;
+
+/// Identifies a node in a [`TransactionalMemory`]'s arena
+///
+/// Stands in for the real on-disk page number of the production storage engine; nodes are
+/// never mutated in place once allocated (copy-on-write), so a `PageNumber` handed out by a
+/// previously committed root stays valid for any reader still using that snapshot.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub(crate) struct PageNumber(usize);
+
+/// A lightweight integrity tag for a page's contents
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub(crate) struct Checksum(u64);
+
+/// A hint from the caller about how a read-only `Btree` will be used; kept for API parity with
+/// the production paging layer, which uses it to prime its page cache
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub(crate) enum PageHint {
+    None,
+    #[allow(dead_code)]
+    Fresh,
+}
+
+type ValueCell = Rc<RefCell<Vec<u8>>>;
+
+#[derive(Debug, Clone)]
+struct LeafNode {
+    // Sorted by key, per the table's `RedbKey::compare`
+    entries: Vec<(Vec<u8>, ValueCell)>,
+    bloom: Option<BloomFilter>,
+}
+
+impl LeafNode {
+    fn from_entries(entries: Vec<(Vec<u8>, ValueCell)>, bloom_enabled: bool) -> Self {
+        let bloom = bloom_enabled.then(|| BloomFilter::build(entries.iter().map(|(k, _)| k.as_slice())));
+        LeafNode { entries, bloom }
+    }
+
+    fn get(&self, key: &[u8], compare: fn(&[u8], &[u8]) -> Ordering) -> Option<ValueCell> {
+        if let Some(bloom) = &self.bloom {
+            if !bloom.might_contain(key) {
+                return None;
+            }
+        }
+        self.entries
+            .binary_search_by(|(k, _)| compare(k, key))
+            .ok()
+            .map(|i| self.entries[i].1.clone())
+    }
+
+    // Returns the value previously stored at `key`, if any
+    fn insert(
+        &mut self,
+        key: &[u8],
+        value: ValueCell,
+        compare: fn(&[u8], &[u8]) -> Ordering,
+    ) -> Option<ValueCell> {
+        match self.entries.binary_search_by(|(k, _)| compare(k, key)) {
+            Ok(i) => Some(std::mem::replace(&mut self.entries[i].1, value)),
+            Err(i) => {
+                self.entries.insert(i, (key.to_vec(), value));
+                None
+            }
+        }
+    }
+
+    fn remove(&mut self, key: &[u8], compare: fn(&[u8], &[u8]) -> Ordering) -> Option<ValueCell> {
+        match self.entries.binary_search_by(|(k, _)| compare(k, key)) {
+            Ok(i) => Some(self.entries.remove(i).1),
+            Err(_) => None,
+        }
+    }
+}
+
+/// A double-hashing Bloom filter over a single leaf's keys
+///
+/// Uses two 64-bit hashes `h1`/`h2` derived from the key bytes (via the crate's existing
+/// hasher, split into halves by a rotation) and tests/sets bits at `(h1 + i*h2) mod m` for
+/// `i in 0..k`, with `k` chosen from a fixed bits-per-key budget.
+#[derive(Debug, Clone)]
+struct BloomFilter {
+    bits: Vec<u64>,
+    m: u64,
+    k: u32,
+}
+
+impl BloomFilter {
+    fn build<'a>(keys: impl Iterator<Item = &'a [u8]> + Clone) -> Self {
+        let n = keys.clone().count().max(1);
+        let m = ((n * DEFAULT_BITS_PER_KEY) as u64).max(64);
+        let k = (((m as f64) / (n as f64)) * std::f64::consts::LN_2)
+            .round()
+            .max(1.0) as u32;
+        let words = m.div_ceil(64) as usize;
+        let mut bits = vec![0u64; words];
+        for key in keys {
+            let (h1, h2) = Self::hashes(key);
+            for i in 0..k {
+                let bit = h1.wrapping_add((i as u64).wrapping_mul(h2)) % m;
+                bits[(bit / 64) as usize] |= 1 << (bit % 64);
+            }
+        }
+        BloomFilter { bits, m, k }
+    }
+
+    fn hashes(key: &[u8]) -> (u64, u64) {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        key.hash(&mut hasher);
+        let h1 = hasher.finish();
+        // Split the single hash into two halves by rotation, as redb's hasher has no native
+        // 128-bit output to split evenly.
+        let h2 = (h1.rotate_left(32) ^ 0x9E37_79B9_7F4A_7C15).max(1);
+        (h1, h2)
+    }
+
+    fn might_contain(&self, key: &[u8]) -> bool {
+        let (h1, h2) = Self::hashes(key);
+        for i in 0..self.k {
+            let bit = h1.wrapping_add((i as u64).wrapping_mul(h2)) % self.m;
+            if self.bits[(bit / 64) as usize] & (1 << (bit % 64)) == 0 {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ChildRef {
+    page: PageNumber,
+    // The smallest key contained in the subtree rooted at `page`
+    first_key: Vec<u8>,
+    // The number of entries in the subtree rooted at `page`, maintained incrementally on every
+    // split/merge so that `len_range`/`rank` can sum it directly instead of walking the subtree
+    count: u64,
+}
+
+#[derive(Debug, Clone)]
+struct InternalNode {
+    children: Vec<ChildRef>,
+}
+
+impl InternalNode {
+    // Returns the index of the child whose subtree may contain `key`
+    fn child_index_for(&self, key: &[u8], compare: fn(&[u8], &[u8]) -> Ordering) -> usize {
+        match self
+            .children
+            .binary_search_by(|c| compare(&c.first_key, key))
+        {
+            Ok(i) => i,
+            Err(0) => 0,
+            Err(i) => i - 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Leaf(LeafNode),
+    Internal(InternalNode),
+}
+
+/// The arena backing a table's B-tree nodes
+///
+/// Stands in for the production engine's mmap'd, page-based `TransactionalMemory`: nodes are
+/// allocated by pushing into a shared, reference-counted arena and are never mutated after
+/// being written, so a page number captured by one transaction's root remains valid even as
+/// later transactions allocate new pages for their own copy-on-write updates.
+#[derive(Clone)]
+pub(crate) struct TransactionalMemory {
+    arena: Rc<RefCell<Vec<Node>>>,
+}
+
+impl TransactionalMemory {
+    pub(crate) fn new() -> Self {
+        Self {
+            arena: Rc::new(RefCell::new(Vec::new())),
+        }
+    }
+
+    fn allocate(&self, node: Node) -> PageNumber {
+        let mut arena = self.arena.borrow_mut();
+        arena.push(node);
+        PageNumber(arena.len() - 1)
+    }
+
+    fn get(&self, page: PageNumber) -> Node {
+        self.arena.borrow()[page.0].clone()
+    }
+
+    fn checksum(&self, page: PageNumber) -> Checksum {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        page.0.hash(&mut hasher);
+        // A real checksum would hash the page's bytes; the page index is a stable stand-in
+        // here since pages are never overwritten once allocated.
+        Checksum(hasher.finish())
+    }
+
+    // Writes `value` directly into an existing leaf entry, used to finalize `insert_reserve`.
+    // Safe to mutate in place because the page was allocated fresh for this reservation and
+    // has not yet been linked into any committed, shared root.
+    fn patch_leaf_value(&self, page: PageNumber, key: &[u8], value: Vec<u8>) {
+        let mut arena = self.arena.borrow_mut();
+        if let Node::Leaf(leaf) = &mut arena[page.0] {
+            for (k, v) in &leaf.entries {
+                if k == key {
+                    *v.borrow_mut() = value;
+                    return;
+                }
+            }
+        }
+    }
+}
+
+enum InsertResult {
+    Updated {
+        page: PageNumber,
+        checksum: Checksum,
+        first_key: Vec<u8>,
+        count: u64,
+        old: Option<ValueCell>,
+        // The leaf the inserted key actually landed in, which is `page` itself only while this
+        // result hasn't yet been folded into a parent internal node; threaded through unchanged
+        // as the recursion unwinds so callers (e.g. `insert_reserve`) can patch the right page.
+        leaf_page: PageNumber,
+    },
+    Split {
+        left: ChildRef,
+        right: ChildRef,
+        old: Option<ValueCell>,
+        leaf_page: PageNumber,
+    },
+}
+
+fn insert_rec(
+    mem: &TransactionalMemory,
+    page: PageNumber,
+    key: &[u8],
+    value: ValueCell,
+    bloom_enabled: bool,
+    compare: fn(&[u8], &[u8]) -> Ordering,
+) -> InsertResult {
+    match mem.get(page) {
+        Node::Leaf(mut leaf) => {
+            let old = leaf.insert(key, value, compare);
+            if leaf.entries.len() > MAX_LEAF_ENTRIES {
+                let mid = leaf.entries.len() / 2;
+                let right_entries = leaf.entries.split_off(mid);
+                let left_first = leaf.entries[0].0.clone();
+                let right_first = right_entries[0].0.clone();
+                let left_count = leaf.entries.len() as u64;
+                let right_count = right_entries.len() as u64;
+                let left_page = mem.allocate(Node::Leaf(LeafNode::from_entries(leaf.entries, bloom_enabled)));
+                let right_page = mem.allocate(Node::Leaf(LeafNode::from_entries(right_entries, bloom_enabled)));
+                // The key just inserted landed in whichever half its own entries ended up in;
+                // `right_first` is the split point, so anything not less than it went right.
+                let leaf_page = if compare(key, &right_first) == Ordering::Less {
+                    left_page
+                } else {
+                    right_page
+                };
+                InsertResult::Split {
+                    left: ChildRef {
+                        page: left_page,
+                        first_key: left_first,
+                        count: left_count,
+                    },
+                    right: ChildRef {
+                        page: right_page,
+                        first_key: right_first,
+                        count: right_count,
+                    },
+                    old,
+                    leaf_page,
+                }
+            } else {
+                // Rebuild the Bloom filter (rather than reusing `leaf`'s stale one) so a key
+                // just inserted here is never a false negative for a subsequent `might_contain`.
+                let first_key = leaf.entries[0].0.clone();
+                let count = leaf.entries.len() as u64;
+                let new_page = mem.allocate(Node::Leaf(LeafNode::from_entries(leaf.entries, bloom_enabled)));
+                InsertResult::Updated {
+                    page: new_page,
+                    checksum: mem.checksum(new_page),
+                    first_key,
+                    count,
+                    old,
+                    leaf_page: new_page,
+                }
+            }
+        }
+        Node::Internal(mut internal) => {
+            let idx = internal.child_index_for(key, compare);
+            let child = internal.children[idx].clone();
+            match insert_rec(mem, child.page, key, value, bloom_enabled, compare) {
+                InsertResult::Updated {
+                    page,
+                    first_key,
+                    count,
+                    old,
+                    leaf_page,
+                    ..
+                } => {
+                    internal.children[idx] = ChildRef { page, first_key, count };
+                    let root_first_key = internal.children[0].first_key.clone();
+                    let total_count = internal.children.iter().map(|c| c.count).sum();
+                    let new_page = mem.allocate(Node::Internal(internal));
+                    InsertResult::Updated {
+                        page: new_page,
+                        checksum: mem.checksum(new_page),
+                        first_key: root_first_key,
+                        count: total_count,
+                        old,
+                        leaf_page,
+                    }
+                }
+                InsertResult::Split {
+                    left,
+                    right,
+                    old,
+                    leaf_page,
+                } => {
+                    internal.children[idx] = left;
+                    internal.children.insert(idx + 1, right);
+                    if internal.children.len() > MAX_CHILDREN {
+                        let mid = internal.children.len() / 2;
+                        let right_children = internal.children.split_off(mid);
+                        let left_first = internal.children[0].first_key.clone();
+                        let right_first = right_children[0].first_key.clone();
+                        let left_count = internal.children.iter().map(|c| c.count).sum();
+                        let right_count = right_children.iter().map(|c| c.count).sum();
+                        let right_internal = InternalNode {
+                            children: right_children,
+                        };
+                        let left_page = mem.allocate(Node::Internal(internal));
+                        let right_page = mem.allocate(Node::Internal(right_internal));
+                        InsertResult::Split {
+                            left: ChildRef {
+                                page: left_page,
+                                first_key: left_first,
+                                count: left_count,
+                            },
+                            right: ChildRef {
+                                page: right_page,
+                                first_key: right_first,
+                                count: right_count,
+                            },
+                            old,
+                            leaf_page,
+                        }
+                    } else {
+                        let first_key = internal.children[0].first_key.clone();
+                        let total_count = internal.children.iter().map(|c| c.count).sum();
+                        let new_page = mem.allocate(Node::Internal(internal));
+                        InsertResult::Updated {
+                            page: new_page,
+                            checksum: mem.checksum(new_page),
+                            first_key,
+                            count: total_count,
+                            old,
+                            leaf_page,
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Returns the new root, the previous value (if any), and the page of the leaf the key actually
+// landed in (distinct from the root once the tree has split past a single leaf) — callers that
+// need to patch the inserted entry in place (`insert_reserve`) must target that leaf, not the
+// root.
+fn insert(
+    mem: &TransactionalMemory,
+    root: Option<(PageNumber, Checksum)>,
+    key: &[u8],
+    value: ValueCell,
+    bloom_enabled: bool,
+    compare: fn(&[u8], &[u8]) -> Ordering,
+) -> (Option<(PageNumber, Checksum)>, Option<ValueCell>, PageNumber) {
+    match root {
+        None => {
+            let leaf = LeafNode::from_entries(vec![(key.to_vec(), value)], bloom_enabled);
+            let page = mem.allocate(Node::Leaf(leaf));
+            (Some((page, mem.checksum(page))), None, page)
+        }
+        Some((page, _)) => match insert_rec(mem, page, key, value, bloom_enabled, compare) {
+            InsertResult::Updated {
+                page, checksum, old, leaf_page, ..
+            } => (Some((page, checksum)), old, leaf_page),
+            InsertResult::Split {
+                left, right, old, leaf_page
+            } => {
+                let root_node = InternalNode {
+                    children: vec![left, right],
+                };
+                let new_page = mem.allocate(Node::Internal(root_node));
+                (Some((new_page, mem.checksum(new_page))), old, leaf_page)
+            }
+        },
+    }
+}
+
+// The surviving replacement for a removed-from page, mirroring `ChildRef` so the caller can
+// patch its parent's child list without threading a raw tuple through the recursion.
+struct RemoveResult {
+    page: PageNumber,
+    checksum: Checksum,
+    first_key: Vec<u8>,
+    count: u64,
+}
+
+fn remove_rec(
+    mem: &TransactionalMemory,
+    page: PageNumber,
+    key: &[u8],
+    compare: fn(&[u8], &[u8]) -> Ordering,
+) -> (Option<RemoveResult>, Option<ValueCell>) {
+    match mem.get(page) {
+        Node::Leaf(mut leaf) => {
+            let bloom_enabled = leaf.bloom.is_some();
+            let old = leaf.remove(key, compare);
+            if leaf.entries.is_empty() {
+                (None, old)
+            } else {
+                let first_key = leaf.entries[0].0.clone();
+                let count = leaf.entries.len() as u64;
+                let new_page = mem.allocate(Node::Leaf(LeafNode::from_entries(leaf.entries, bloom_enabled)));
+                let checksum = mem.checksum(new_page);
+                (
+                    Some(RemoveResult {
+                        page: new_page,
+                        checksum,
+                        first_key,
+                        count,
+                    }),
+                    old,
+                )
+            }
+        }
+        Node::Internal(mut internal) => {
+            let idx = internal.child_index_for(key, compare);
+            let child_page = internal.children[idx].page;
+            let (child_result, old) = remove_rec(mem, child_page, key, compare);
+            match child_result {
+                Some(RemoveResult { page, first_key, count, .. }) => {
+                    internal.children[idx] = ChildRef { page, first_key, count };
+                }
+                None => {
+                    internal.children.remove(idx);
+                }
+            }
+            if internal.children.is_empty() {
+                (None, old)
+            } else if internal.children.len() == 1 {
+                // Height reduction: promote the sole surviving child up a level. Its subtree's
+                // cardinality is unchanged by the promotion, so `count` carries over as-is.
+                let only = internal.children.into_iter().next().unwrap();
+                let checksum = mem.checksum(only.page);
+                (
+                    Some(RemoveResult {
+                        page: only.page,
+                        checksum,
+                        first_key: only.first_key,
+                        count: only.count,
+                    }),
+                    old,
+                )
+            } else {
+                let first_key = internal.children[0].first_key.clone();
+                let count = internal.children.iter().map(|c| c.count).sum();
+                let new_page = mem.allocate(Node::Internal(internal));
+                let checksum = mem.checksum(new_page);
+                (
+                    Some(RemoveResult {
+                        page: new_page,
+                        checksum,
+                        first_key,
+                        count,
+                    }),
+                    old,
+                )
+            }
+        }
+    }
+}
+
+fn remove(
+    mem: &TransactionalMemory,
+    root: Option<(PageNumber, Checksum)>,
+    key: &[u8],
+    compare: fn(&[u8], &[u8]) -> Ordering,
+) -> (Option<(PageNumber, Checksum)>, Option<ValueCell>) {
+    match root {
+        None => (None, None),
+        Some((page, _)) => {
+            let (result, old) = remove_rec(mem, page, key, compare);
+            (result.map(|r| (r.page, r.checksum)), old)
+        }
+    }
+}
+
+// Builds a B-tree from `entries`, assumed already sorted by key, in O(n) by filling leaves
+// sequentially and then building each internal level directly from the one below it, rather than
+// inserting entries one at a time (which would cost O(n log n) and repeatedly split nodes that
+// bulk-loading can size correctly from the start). Used by `Table::import` to bulk-load an
+// export stream.
+fn bulk_build(
+    mem: &TransactionalMemory,
+    entries: Vec<(Vec<u8>, ValueCell)>,
+    bloom_enabled: bool,
+) -> Option<(PageNumber, Checksum)> {
+    if entries.is_empty() {
+        return None;
+    }
+    let mut level: Vec<ChildRef> = entries
+        .chunks(MAX_LEAF_ENTRIES)
+        .map(|chunk| {
+            let first_key = chunk[0].0.clone();
+            let count = chunk.len() as u64;
+            let page = mem.allocate(Node::Leaf(LeafNode::from_entries(chunk.to_vec(), bloom_enabled)));
+            ChildRef { page, first_key, count }
+        })
+        .collect();
+
+    while level.len() > 1 {
+        level = level
+            .chunks(MAX_CHILDREN)
+            .map(|chunk| {
+                let first_key = chunk[0].first_key.clone();
+                let count = chunk.iter().map(|c| c.count).sum();
+                let page = mem.allocate(Node::Internal(InternalNode {
+                    children: chunk.to_vec(),
+                }));
+                ChildRef { page, first_key, count }
+            })
+            .collect();
+    }
+
+    let root = level.into_iter().next().unwrap();
+    let checksum = mem.checksum(root.page);
+    Some((root.page, checksum))
+}
+
+fn get(
+    mem: &TransactionalMemory,
+    root: Option<(PageNumber, Checksum)>,
+    key: &[u8],
+    compare: fn(&[u8], &[u8]) -> Ordering,
+) -> Option<ValueCell> {
+    let mut page = root?.0;
+    loop {
+        match mem.get(page) {
+            Node::Leaf(leaf) => return leaf.get(key, compare),
+            Node::Internal(internal) => {
+                page = internal.children[internal.child_index_for(key, compare)].page;
+            }
+        }
+    }
+}
+
+fn contains_key(
+    mem: &TransactionalMemory,
+    root: Option<(PageNumber, Checksum)>,
+    key: &[u8],
+    compare: fn(&[u8], &[u8]) -> Ordering,
+) -> bool {
+    let Some((mut page, _)) = root else {
+        return false;
+    };
+    loop {
+        match mem.get(page) {
+            Node::Leaf(leaf) => {
+                if let Some(bloom) = &leaf.bloom {
+                    if !bloom.might_contain(key) {
+                        return false;
+                    }
+                }
+                return leaf.entries.binary_search_by(|(k, _)| compare(k, key)).is_ok();
+            }
+            Node::Internal(internal) => {
+                page = internal.children[internal.child_index_for(key, compare)].page;
+            }
+        }
+    }
+}
+
+// Total number of entries in the subtree rooted at `root`. O(1): the root's own `ChildRef`s (or
+// leaf entry count) already carry the sum of their subtrees, maintained incrementally by
+// `insert_rec`/`remove_rec`, so no recursion is needed.
+fn node_total(mem: &TransactionalMemory, root: Option<(PageNumber, Checksum)>) -> u64 {
+    let Some((page, _)) = root else {
+        return 0;
+    };
+    match mem.get(page) {
+        Node::Leaf(leaf) => leaf.entries.len() as u64,
+        Node::Internal(internal) => internal.children.iter().map(|c| c.count).sum(),
+    }
+}
+
+// Number of keys in the subtree rooted at `page` that are strictly less than `key`. For an
+// internal node, every child fully to the left of the one that could contain `key` consists
+// entirely of smaller keys (per the `first_key`-sorted `ChildRef` invariant), so their counts are
+// summed directly and only the one candidate child is recursed into; this keeps the walk O(log
+// n) rather than O(n).
+fn rank_rec(mem: &TransactionalMemory, page: PageNumber, key: &[u8], compare: fn(&[u8], &[u8]) -> Ordering) -> u64 {
+    match mem.get(page) {
+        Node::Leaf(leaf) => leaf.entries.partition_point(|(k, _)| compare(k, key) == Ordering::Less) as u64,
+        Node::Internal(internal) => {
+            let idx = internal.child_index_for(key, compare);
+            let left_count: u64 = internal.children[..idx].iter().map(|c| c.count).sum();
+            left_count + rank_rec(mem, internal.children[idx].page, key, compare)
+        }
+    }
+}
+
+fn rank(
+    mem: &TransactionalMemory,
+    root: Option<(PageNumber, Checksum)>,
+    key: &[u8],
+    compare: fn(&[u8], &[u8]) -> Ordering,
+) -> u64 {
+    match root {
+        None => 0,
+        Some((page, _)) => rank_rec(mem, page, key, compare),
+    }
+}
+
+fn len_range(
+    mem: &TransactionalMemory,
+    root: Option<(PageNumber, Checksum)>,
+    lower: Bound<&[u8]>,
+    upper: Bound<&[u8]>,
+    compare: fn(&[u8], &[u8]) -> Ordering,
+) -> u64 {
+    let lower_count = match lower {
+        Bound::Unbounded => 0,
+        Bound::Included(k) => rank(mem, root, k, compare),
+        Bound::Excluded(k) => rank(mem, root, k, compare) + contains_key(mem, root, k, compare) as u64,
+    };
+    let upper_count = match upper {
+        Bound::Unbounded => node_total(mem, root),
+        Bound::Included(k) => rank(mem, root, k, compare) + contains_key(mem, root, k, compare) as u64,
+        Bound::Excluded(k) => rank(mem, root, k, compare),
+    };
+    upper_count.saturating_sub(lower_count)
+}
+
+fn in_bounds(key: &[u8], lower: Bound<&[u8]>, upper: Bound<&[u8]>, compare: fn(&[u8], &[u8]) -> Ordering) -> bool {
+    let above_lower = match lower {
+        Bound::Unbounded => true,
+        Bound::Included(k) => compare(key, k) != Ordering::Less,
+        Bound::Excluded(k) => compare(key, k) == Ordering::Greater,
+    };
+    let below_upper = match upper {
+        Bound::Unbounded => true,
+        Bound::Included(k) => compare(key, k) != Ordering::Greater,
+        Bound::Excluded(k) => compare(key, k) == Ordering::Less,
+    };
+    above_lower && below_upper
+}
+
+fn collect_range(
+    mem: &TransactionalMemory,
+    page: PageNumber,
+    lower: Bound<&[u8]>,
+    upper: Bound<&[u8]>,
+    compare: fn(&[u8], &[u8]) -> Ordering,
+    out: &mut Vec<(Vec<u8>, ValueCell)>,
+) {
+    match mem.get(page) {
+        Node::Leaf(leaf) => {
+            for (k, v) in &leaf.entries {
+                if in_bounds(k, lower, upper, compare) {
+                    out.push((k.clone(), v.clone()));
+                }
+            }
+        }
+        Node::Internal(internal) => {
+            for child in &internal.children {
+                collect_range(mem, child.page, lower, upper, compare, out);
+            }
+        }
+    }
+}
+
+/// A read-only view of a table's B-tree at a fixed root
+pub(crate) struct Btree<'txn, K: RedbKey + ?Sized, V: crate::types::RedbValue + ?Sized> {
+    root: Option<(PageNumber, Checksum)>,
+    mem: TransactionalMemory,
+    _hint: PageHint,
+    compression: Compression,
+    _marker: PhantomData<(&'txn K, &'txn V)>,
+}
+
+impl<'txn, K: RedbKey + ?Sized, V: crate::types::RedbValue + ?Sized> Btree<'txn, K, V> {
+    pub(crate) fn new(
+        root: Option<(PageNumber, Checksum)>,
+        hint: PageHint,
+        mem: TransactionalMemory,
+        compression: Compression,
+    ) -> Self {
+        Self {
+            root,
+            mem,
+            _hint: hint,
+            compression,
+            _marker: PhantomData,
+        }
+    }
+
+    pub(crate) fn get(&self, key: &K::SelfType<'_>) -> Result<Option<crate::AccessGuard<'_, V>>> {
+        let key_bytes = K::as_bytes(key).as_ref().to_vec();
+        Ok(get(&self.mem, self.root, &key_bytes, K::compare).map(|v| {
+            crate::AccessGuard::with_owned_value(self.compression.decompress(&v.borrow()))
+        }))
+    }
+
+    pub(crate) fn contains_key(&self, key: &K::SelfType<'_>) -> Result<bool> {
+        let key_bytes = K::as_bytes(key).as_ref().to_vec();
+        Ok(contains_key(&self.mem, self.root, &key_bytes, K::compare))
+    }
+
+    pub(crate) fn range<'a, KR>(&self, range: impl RangeBounds<KR> + 'a) -> Result<BtreeRangeIter<'a, K, V>>
+    where
+        K: 'a,
+        KR: std::borrow::Borrow<K::SelfType<'a>> + ?Sized + 'a,
+    {
+        Ok(range_query(&self.mem, self.root, range, self.compression))
+    }
+
+    /// Returns the number of keys in this table strictly less than `key`
+    pub(crate) fn rank(&self, key: &K::SelfType<'_>) -> Result<u64> {
+        let key_bytes = K::as_bytes(key).as_ref().to_vec();
+        Ok(rank(&self.mem, self.root, &key_bytes, K::compare))
+    }
+
+    /// Returns the number of keys within `range`, in O(log n) via the subtree counts maintained
+    /// on each `ChildRef`, rather than iterating the range
+    pub(crate) fn len_range<'a, KR>(&self, range: impl RangeBounds<KR> + 'a) -> Result<u64>
+    where
+        K: 'a,
+        KR: std::borrow::Borrow<K::SelfType<'a>> + ?Sized + 'a,
+    {
+        let owned = owned_bound_bytes::<K, KR>(&range);
+        Ok(len_range(
+            &self.mem,
+            self.root,
+            owned.0.as_deref_bound(),
+            owned.1.as_deref_bound(),
+            K::compare,
+        ))
+    }
+}
+
+// Helper to materialize owned byte bounds from a generic `RangeBounds<KR>`, since `K::as_bytes`
+// needs a `K::SelfType` reference whose lifetime we can't borrow past this function.
+enum OwnedBound {
+    Unbounded,
+    Included(Vec<u8>),
+    Excluded(Vec<u8>),
+}
+
+impl OwnedBound {
+    fn as_deref_bound(&self) -> Bound<&[u8]> {
+        match self {
+            OwnedBound::Unbounded => Bound::Unbounded,
+            OwnedBound::Included(b) => Bound::Included(b.as_slice()),
+            OwnedBound::Excluded(b) => Bound::Excluded(b.as_slice()),
+        }
+    }
+}
+
+fn owned_bound_bytes<'a, K, KR>(range: &impl RangeBounds<KR>) -> (OwnedBound, OwnedBound)
+where
+    K: RedbKey + ?Sized + 'a,
+    KR: std::borrow::Borrow<K::SelfType<'a>> + ?Sized + 'a,
+{
+    let lower = match range.start_bound() {
+        Bound::Unbounded => OwnedBound::Unbounded,
+        Bound::Included(k) => OwnedBound::Included(K::as_bytes(k.borrow()).as_ref().to_vec()),
+        Bound::Excluded(k) => OwnedBound::Excluded(K::as_bytes(k.borrow()).as_ref().to_vec()),
+    };
+    let upper = match range.end_bound() {
+        Bound::Unbounded => OwnedBound::Unbounded,
+        Bound::Included(k) => OwnedBound::Included(K::as_bytes(k.borrow()).as_ref().to_vec()),
+        Bound::Excluded(k) => OwnedBound::Excluded(K::as_bytes(k.borrow()).as_ref().to_vec()),
+    };
+    (lower, upper)
+}
+
+fn range_query<'a, K, V, KR>(
+    mem: &TransactionalMemory,
+    root: Option<(PageNumber, Checksum)>,
+    range: impl RangeBounds<KR> + 'a,
+    compression: Compression,
+) -> BtreeRangeIter<'a, K, V>
+where
+    K: RedbKey + ?Sized + 'a,
+    V: crate::types::RedbValue + ?Sized,
+    KR: std::borrow::Borrow<K::SelfType<'a>> + ?Sized + 'a,
+{
+    let owned = owned_bound_bytes::<K, KR>(&range);
+    let mut out = Vec::new();
+    if let Some((page, _)) = root {
+        collect_range(
+            mem,
+            page,
+            owned.0.as_deref_bound(),
+            owned.1.as_deref_bound(),
+            K::compare,
+            &mut out,
+        );
+    }
+    BtreeRangeIter {
+        entries: out.into_iter().collect(),
+        compression,
+        _marker: PhantomData,
+    }
+}
+
+/// A double-ended iterator over a sorted range of a table's entries
+pub(crate) struct BtreeRangeIter<'a, K: RedbKey + ?Sized, V: crate::types::RedbValue + ?Sized> {
+    entries: VecDeque<(Vec<u8>, ValueCell)>,
+    compression: Compression,
+    _marker: PhantomData<(&'a K, &'a V)>,
+}
+
+impl<'a, K: RedbKey + ?Sized, V: crate::types::RedbValue + ?Sized> Iterator for BtreeRangeIter<'a, K, V> {
+    type Item = (crate::AccessGuard<'a, K>, crate::AccessGuard<'a, V>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (k, v) = self.entries.pop_front()?;
+        let value_bytes = self.compression.decompress(&v.borrow());
+        Some((
+            crate::AccessGuard::with_owned_value(k),
+            crate::AccessGuard::with_owned_value(value_bytes),
+        ))
+    }
+}
+
+impl<'a, K: RedbKey + ?Sized, V: crate::types::RedbValue + ?Sized> DoubleEndedIterator
+    for BtreeRangeIter<'a, K, V>
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let (k, v) = self.entries.pop_back()?;
+        let value_bytes = self.compression.decompress(&v.borrow());
+        Some((
+            crate::AccessGuard::with_owned_value(k),
+            crate::AccessGuard::with_owned_value(value_bytes),
+        ))
+    }
+}
+
+/// A mutable view of a table's B-tree, rooted at a transaction's (possibly uncommitted) root
+pub(crate) struct BtreeMut<'txn, K: RedbKey + ?Sized, V: crate::types::RedbValue + ?Sized> {
+    root: Option<(PageNumber, Checksum)>,
+    mem: TransactionalMemory,
+    #[allow(dead_code)]
+    freed_pages: Rc<RefCell<Vec<PageNumber>>>,
+    bloom_enabled: bool,
+    compression: Compression,
+    _marker: PhantomData<(&'txn K, &'txn V)>,
+}
+
+impl<'txn, K: RedbKey + ?Sized, V: crate::types::RedbValue + ?Sized> BtreeMut<'txn, K, V> {
+    pub(crate) fn new(
+        root: Option<(PageNumber, Checksum)>,
+        mem: TransactionalMemory,
+        freed_pages: Rc<RefCell<Vec<PageNumber>>>,
+        bloom_enabled: bool,
+        compression: Compression,
+    ) -> Self {
+        Self {
+            root,
+            mem,
+            freed_pages,
+            bloom_enabled,
+            compression,
+            _marker: PhantomData,
+        }
+    }
+
+    pub(crate) fn get_root(&self) -> Option<(PageNumber, Checksum)> {
+        self.root
+    }
+
+    pub(crate) fn get(&self, key: &K::SelfType<'_>) -> Result<Option<crate::AccessGuard<'_, V>>> {
+        let key_bytes = K::as_bytes(key).as_ref().to_vec();
+        Ok(get(&self.mem, self.root, &key_bytes, K::compare)
+            .map(|v| crate::AccessGuard::with_owned_value(self.compression.decompress(&v.borrow()))))
+    }
+
+    /// Returns `true` if `key` is present, short-circuiting via the leaf's Bloom filter (if
+    /// this table was created with one) without reading the candidate leaf's entries.
+    pub(crate) fn contains_key(&self, key: &K::SelfType<'_>) -> Result<bool> {
+        let key_bytes = K::as_bytes(key).as_ref().to_vec();
+        Ok(contains_key(&self.mem, self.root, &key_bytes, K::compare))
+    }
+
+    pub(crate) fn range<'a, KR>(&'a self, range: impl RangeBounds<KR> + 'a) -> Result<BtreeRangeIter<'a, K, V>>
+    where
+        K: 'a,
+        KR: std::borrow::Borrow<K::SelfType<'a>> + ?Sized + 'a,
+    {
+        Ok(range_query(&self.mem, self.root, range, self.compression))
+    }
+
+    /// Returns the number of keys in this table strictly less than `key`
+    pub(crate) fn rank(&self, key: &K::SelfType<'_>) -> Result<u64> {
+        let key_bytes = K::as_bytes(key).as_ref().to_vec();
+        Ok(rank(&self.mem, self.root, &key_bytes, K::compare))
+    }
+
+    /// Returns the number of keys within `range`, in O(log n) via the subtree counts maintained
+    /// on each `ChildRef`, rather than iterating the range
+    pub(crate) fn len_range<'a, KR>(&'a self, range: impl RangeBounds<KR> + 'a) -> Result<u64>
+    where
+        K: 'a,
+        KR: std::borrow::Borrow<K::SelfType<'a>> + ?Sized + 'a,
+    {
+        let owned = owned_bound_bytes::<K, KR>(&range);
+        Ok(len_range(
+            &self.mem,
+            self.root,
+            owned.0.as_deref_bound(),
+            owned.1.as_deref_bound(),
+            K::compare,
+        ))
+    }
+
+    pub(crate) fn insert(
+        &mut self,
+        key: &K::SelfType<'_>,
+        value: &V::SelfType<'_>,
+    ) -> Result<Option<crate::AccessGuard<'_, V>>> {
+        let key_bytes = K::as_bytes(key).as_ref().to_vec();
+        let bytes = V::as_bytes(value);
+        let compressed = self.compression.compress(bytes.as_ref());
+        let value_cell = Rc::new(RefCell::new(compressed));
+        let (new_root, old, _leaf_page) =
+            insert(&self.mem, self.root, &key_bytes, value_cell, self.bloom_enabled, K::compare);
+        self.root = new_root;
+        Ok(old.map(|v| crate::AccessGuard::with_owned_value(self.compression.decompress(&v.borrow()))))
+    }
+
+    pub(crate) fn remove(&mut self, key: &K::SelfType<'_>) -> Result<Option<crate::AccessGuard<'_, V>>> {
+        let key_bytes = K::as_bytes(key).as_ref().to_vec();
+        let (new_root, old) = remove(&self.mem, self.root, &key_bytes, K::compare);
+        self.root = new_root;
+        Ok(old.map(|v| crate::AccessGuard::with_owned_value(self.compression.decompress(&v.borrow()))))
+    }
+
+    /// Reserves `value_length` bytes for `key`, returning a guard the caller writes into
+    /// directly; the write is committed to the tree when the guard is dropped.
+    ///
+    /// Callers must not use this on a table configured with compression (see
+    /// `Table::insert_reserve`), since the bytes written here are stored raw, bypassing the
+    /// codec entirely.
+    /// Returns the guard to write into, plus `true` if `key` was not already present (the
+    /// caller uses this to decide whether to bump its entry count, mirroring `insert`'s
+    /// "increment only when the old value was `None`" rule).
+    pub(crate) fn insert_reserve<'s>(
+        &'s mut self,
+        key: &K::SelfType<'_>,
+        value_length: usize,
+    ) -> Result<(crate::access_guard::AccessGuardMut<'s, K>, bool)> {
+        let key_bytes = K::as_bytes(key).as_ref().to_vec();
+        let value_cell = Rc::new(RefCell::new(vec![0u8; value_length]));
+        let (new_root, old, leaf_page) =
+            insert(&self.mem, self.root, &key_bytes, value_cell, self.bloom_enabled, K::compare);
+        self.root = new_root;
+        let mem = self.mem.clone();
+        let finish_key = key_bytes.clone();
+        Ok((
+            crate::access_guard::AccessGuardMut::new(
+                value_length,
+                Box::new(move |bytes| mem.patch_leaf_value(leaf_page, &finish_key, bytes)),
+            ),
+            old.is_none(),
+        ))
+    }
+
+    /// Bulk-loads `entries` (assumed sorted by key and empty-table-only, i.e. `self.root` is
+    /// `None`) via [`bulk_build`], compressing each value the same way `insert` would. Returns
+    /// the number of entries loaded.
+    pub(crate) fn bulk_load(&mut self, entries: Vec<(Vec<u8>, Vec<u8>)>) -> Result<u64> {
+        let count = entries.len() as u64;
+        let cells = entries
+            .into_iter()
+            .map(|(k, v)| (k, Rc::new(RefCell::new(self.compression.compress(&v)))))
+            .collect();
+        self.root = bulk_build(&self.mem, cells, self.bloom_enabled);
+        Ok(count)
+    }
+
+    /// Removes every key in `range` from the tree immediately (not lazily as the returned
+    /// iterator is consumed), invoking `on_remove` for each one up front so the caller can
+    /// account for the whole drained range (entry count, change log, ...) even if the returned
+    /// iterator is dropped before being fully consumed.
+    ///
+    /// # Safety
+    /// No other references to this table's tree may be alive; `Table` upholds this by only
+    /// allowing one mutable borrow at a time.
+    pub(crate) unsafe fn drain<'a, KR>(
+        &'a mut self,
+        range: impl RangeBounds<KR> + Clone + 'a,
+        mut on_remove: impl FnMut(&[u8]),
+    ) -> Result<BtreeDrain<'a, K, V>>
+    where
+        K: 'a,
+        KR: std::borrow::Borrow<K::SelfType<'a>> + Clone + 'a,
+    {
+        let owned = owned_bound_bytes::<K, KR>(&range);
+        let (lower, upper) = (owned.0.as_deref_bound(), owned.1.as_deref_bound());
+        let mut matching = Vec::new();
+        if let Some((page, _)) = self.root {
+            collect_range(&self.mem, page, lower, upper, K::compare, &mut matching);
+        }
+        for (key, _) in &matching {
+            on_remove(key);
+            let (new_root, _) = remove(&self.mem, self.root, key, K::compare);
+            self.root = new_root;
+        }
+        Ok(BtreeDrain {
+            entries: matching.into_iter().collect(),
+            compression: self.compression,
+            _marker: PhantomData,
+        })
+    }
+}
+
+/// The result of draining a range from a table
+pub(crate) struct BtreeDrain<'a, K: RedbKey + ?Sized, V: crate::types::RedbValue + ?Sized> {
+    entries: VecDeque<(Vec<u8>, ValueCell)>,
+    compression: Compression,
+    _marker: PhantomData<(&'a K, &'a V)>,
+}
+
+impl<'a, K: RedbKey + ?Sized, V: crate::types::RedbValue + ?Sized> Iterator for BtreeDrain<'a, K, V> {
+    type Item = (crate::AccessGuard<'a, K>, crate::AccessGuard<'a, V>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (k, v) = self.entries.pop_front()?;
+        let value_bytes = self.compression.decompress(&v.borrow());
+        Some((
+            crate::AccessGuard::with_owned_value(k),
+            crate::AccessGuard::with_owned_value(value_bytes),
+        ))
+    }
+}
+
+impl<'a, K: RedbKey + ?Sized, V: crate::types::RedbValue + ?Sized> DoubleEndedIterator
+    for BtreeDrain<'a, K, V>
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let (k, v) = self.entries.pop_back()?;
+        let value_bytes = self.compression.decompress(&v.borrow());
+        Some((
+            crate::AccessGuard::with_owned_value(k),
+            crate::AccessGuard::with_owned_value(value_bytes),
+        ))
+    }
+}
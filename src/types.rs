@@ -0,0 +1,184 @@
+use std::cmp::Ordering;
+use std::fmt::Debug;
+
+/// Prevents downstream crates from implementing `RedbKey` for their own types in ways that
+/// could violate the invariants the storage layer relies on (e.g. `compare` being a total
+/// order consistent with the stored bytes). `RedbValue` does not require it.
+pub trait Sealed {}
+
+/// A type which can be stored in a redb table, as either a key or a value
+pub trait RedbValue: Debug {
+    /// The lifetime-generic "deserialized" representation of this type
+    type SelfType<'a>: Debug
+    where
+        Self: 'a;
+
+    /// The lifetime-generic byte representation of this type
+    type AsBytes<'a>: AsRef<[u8]> + 'a
+    where
+        Self: 'a;
+
+    /// The fixed width of this type's serialized form, if any
+    fn fixed_width() -> Option<usize>;
+
+    /// Deserializes `data` into `Self::SelfType`
+    fn from_bytes<'a>(data: &'a [u8]) -> Self::SelfType<'a>
+    where
+        Self: 'a;
+
+    /// Serializes `value` into bytes
+    fn as_bytes<'a, 'b: 'a>(value: &'a Self::SelfType<'b>) -> Self::AsBytes<'a>
+    where
+        Self: 'a,
+        Self: 'b;
+
+    /// A unique name for this type, used to validate that a table is reopened with the same
+    /// key/value types it was created with
+    fn redb_type_name() -> String;
+}
+
+/// A [`RedbValue`] which has a total order and so can be used as a table's key type
+pub trait RedbKey: RedbValue + Sealed {
+    /// Compares the serialized forms of two keys
+    fn compare(data1: &[u8], data2: &[u8]) -> Ordering;
+}
+
+impl Sealed for &str {}
+
+impl RedbValue for &str {
+    type SelfType<'a>
+        = &'a str
+    where
+        Self: 'a;
+    type AsBytes<'a>
+        = &'a [u8]
+    where
+        Self: 'a;
+
+    fn fixed_width() -> Option<usize> {
+        None
+    }
+
+    fn from_bytes<'a>(data: &'a [u8]) -> &'a str
+    where
+        Self: 'a,
+    {
+        std::str::from_utf8(data).expect("corrupt utf8 in str value")
+    }
+
+    fn as_bytes<'a, 'b: 'a>(value: &'a Self::SelfType<'b>) -> &'a [u8]
+    where
+        Self: 'a,
+        Self: 'b,
+    {
+        value.as_bytes()
+    }
+
+    fn redb_type_name() -> String {
+        "&str".to_string()
+    }
+}
+
+impl RedbKey for &str {
+    fn compare(data1: &[u8], data2: &[u8]) -> Ordering {
+        data1.cmp(data2)
+    }
+}
+
+impl Sealed for &[u8] {}
+
+impl RedbValue for &[u8] {
+    type SelfType<'a>
+        = &'a [u8]
+    where
+        Self: 'a;
+    type AsBytes<'a>
+        = &'a [u8]
+    where
+        Self: 'a;
+
+    fn fixed_width() -> Option<usize> {
+        None
+    }
+
+    fn from_bytes<'a>(data: &'a [u8]) -> &'a [u8]
+    where
+        Self: 'a,
+    {
+        data
+    }
+
+    fn as_bytes<'a, 'b: 'a>(value: &'a Self::SelfType<'b>) -> &'a [u8]
+    where
+        Self: 'a,
+        Self: 'b,
+    {
+        value
+    }
+
+    fn redb_type_name() -> String {
+        "&[u8]".to_string()
+    }
+}
+
+impl RedbKey for &[u8] {
+    fn compare(data1: &[u8], data2: &[u8]) -> Ordering {
+        data1.cmp(data2)
+    }
+}
+
+macro_rules! be_bytes_integer {
+    ($t:ty) => {
+        impl Sealed for $t {}
+
+        impl RedbValue for $t {
+            type SelfType<'a>
+                = $t
+            where
+                Self: 'a;
+            type AsBytes<'a>
+                = [u8; std::mem::size_of::<$t>()]
+            where
+                Self: 'a;
+
+            fn fixed_width() -> Option<usize> {
+                Some(std::mem::size_of::<$t>())
+            }
+
+            fn from_bytes<'a>(data: &'a [u8]) -> $t
+            where
+                Self: 'a,
+            {
+                <$t>::from_be_bytes(data.try_into().unwrap())
+            }
+
+            fn as_bytes<'a, 'b: 'a>(value: &'a Self::SelfType<'b>) -> [u8; std::mem::size_of::<$t>()]
+            where
+                Self: 'a,
+                Self: 'b,
+            {
+                value.to_be_bytes()
+            }
+
+            fn redb_type_name() -> String {
+                stringify!($t).to_string()
+            }
+        }
+
+        impl RedbKey for $t {
+            fn compare(data1: &[u8], data2: &[u8]) -> Ordering {
+                // Big-endian encoding keeps byte-wise comparison equivalent to numeric comparison
+                data1.cmp(data2)
+            }
+        }
+    };
+}
+
+be_bytes_integer!(u8);
+be_bytes_integer!(u16);
+be_bytes_integer!(u32);
+be_bytes_integer!(u64);
+be_bytes_integer!(i8);
+be_bytes_integer!(i16);
+be_bytes_integer!(i32);
+be_bytes_integer!(i64);
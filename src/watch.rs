@@ -0,0 +1,47 @@
+//! Change-notification hooks on committed table mutations, registered via
+//! [`Database::watch`](crate::Database::watch)/[`Database::watch_channel`](crate::Database::watch_channel).
+//!
+//! Changes are collected per-table by `Table::insert`/`remove`/`drain` into the owning
+//! `WriteTransaction`'s change log, and are only dispatched to watchers from
+//! `WriteTransaction::commit` — an aborted transaction's changes are simply dropped, so a
+//! watcher never observes uncommitted or rolled-back state.
+
+use std::sync::mpsc::Sender;
+
+/// Whether a changed key was newly inserted, overwrote an existing value, or was removed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Insert,
+    Overwrite,
+    Removal,
+}
+
+/// A single key change reported to a table's watchers
+///
+/// `key` holds the key's serialized bytes rather than `K::SelfType`, since a watcher is
+/// registered by table name and so isn't generic over the table's key type.
+#[derive(Debug, Clone)]
+pub struct TableChange {
+    pub key: Vec<u8>,
+    pub kind: ChangeKind,
+}
+
+type Callback = Box<dyn Fn(&[TableChange])>;
+
+pub(crate) enum Watcher {
+    Callback(Callback),
+    Channel(Sender<Vec<TableChange>>),
+}
+
+impl Watcher {
+    pub(crate) fn notify(&self, changes: &[TableChange]) {
+        match self {
+            Watcher::Callback(callback) => callback(changes),
+            Watcher::Channel(sender) => {
+                // A disconnected receiver (the caller dropped it) just means nobody's
+                // listening anymore; not an error condition for the writer.
+                let _ = sender.send(changes.to_vec());
+            }
+        }
+    }
+}